@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Write};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -45,7 +45,7 @@ where
         }
     }
 
-    async fn handle_line(self: &Arc<Self>, line: Option<String>) {
+    pub(crate) async fn handle_line(self: &Arc<Self>, line: Option<String>) {
         let Some(line) = line else { return };
 
         let be_at_text = format!("@{} ", self.self_user.id);
@@ -62,6 +62,9 @@ where
             contents = contents.at(crate::User::new(self.self_user.id.clone()));
             last_pos = pos + be_at_text.len();
         }
+        if last_pos < line.len() {
+            contents = contents.text(&line[last_pos..]);
+        }
 
         let sender = crate::User::new(users::get_current_uid().to_string()).nickname(
             users::get_current_username()
@@ -96,6 +99,10 @@ where
         &self.self_user
     }
 
+    fn get_platform(&self) -> &'static str {
+        "cli"
+    }
+
     async fn run(self: Arc<Self>) {
         let mut reader = BufReader::new(tokio::io::stdin()).lines();
         loop {
@@ -118,11 +125,11 @@ where
         &self,
         contents: crate::MessageContents,
         _: crate::Chat<C>,
-        _: Option<&crate::Message<C>>,
-    ) -> Result<String> {
-        println!("```\n{contents}\n```");
+        _: Option<&str>,
+    ) -> Result<Vec<String>> {
+        println!("```\n{}\n```", render(&contents));
 
-        Ok(now_as_id())
+        Ok(vec![now_as_id()])
     }
 
     async fn is_group_admin(&self, _: &crate::User, _: &crate::Group) -> Result<bool> {
@@ -130,6 +137,41 @@ where
     }
 }
 
+/// Renders `contents` with ANSI escape codes.
+fn render(contents: &crate::MessageContents) -> String {
+    let mut out = String::new();
+
+    for content in contents {
+        let rendered = match content {
+            crate::MessageContent::Styled { text, style } => match style {
+                crate::Style::Bold => format!("\x1b[1m{text}\x1b[0m"),
+                crate::Style::Italic => format!("\x1b[3m{text}\x1b[0m"),
+                crate::Style::Underline => format!("\x1b[4m{text}\x1b[0m"),
+                crate::Style::Strikethrough => format!("\x1b[9m{text}\x1b[0m"),
+                crate::Style::Spoiler => format!("\x1b[7m{text}\x1b[0m"),
+                crate::Style::Code => format!("\x1b[36m{text}\x1b[0m"),
+                crate::Style::Pre { .. } => format!("\n\x1b[36m{text}\x1b[0m\n"),
+                // OSC 8 hyperlink
+                crate::Style::Link { url } => format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\"),
+            },
+            // A quoted block with a line for each node
+            crate::MessageContent::Forward(nodes) => {
+                let mut block = String::new();
+                for node in nodes {
+                    let contents = render(node.get_contents()).replace('\n', "\n│ ");
+                    let _ = write!(block, "\n│ \x1b[1m{}\x1b[0m: {contents}", node.get_name());
+                }
+                block.push('\n');
+                block
+            },
+            _ => content.to_string(),
+        };
+        out.push_str(&rendered);
+    }
+
+    out
+}
+
 fn now_as_id() -> String {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
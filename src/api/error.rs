@@ -0,0 +1,87 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+/// A failure reported by a platform. Errors returned from
+/// [`BotAPI`](super::BotAPI) methods can be downcast to it with
+/// `err.downcast_ref::<api::Error>()`.
+#[derive(Clone, Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    code: Option<i64>,
+    description: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The bot may not talk to the chat, e.g. it was blocked by the user or
+    /// removed from the group.
+    Forbidden,
+    ChatNotFound,
+    RateLimited {
+        retry_after: Duration,
+    },
+    /// The platform could not be reached or gave no well-formed answer.
+    Network,
+    Other,
+}
+
+impl Error {
+    #[must_use]
+    pub const fn new(kind: ErrorKind, code: Option<i64>, description: Option<String>) -> Self {
+        Self {
+            kind,
+            code,
+            description,
+        }
+    }
+
+    #[must_use]
+    pub const fn get_kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The error code of the platform, such as the `error_code` of Telegram
+    /// or the `retcode` of [`OneBot11`](super::onebot_11::OneBot11).
+    #[must_use]
+    pub const fn get_code(&self) -> Option<i64> {
+        self.code
+    }
+
+    #[must_use]
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    #[must_use]
+    pub const fn get_retry_after(&self) -> Option<Duration> {
+        match self.kind {
+            ErrorKind::RateLimited { retry_after } => Some(retry_after),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ErrorKind::Forbidden => write!(f, "forbidden")?,
+            ErrorKind::ChatNotFound => write!(f, "chat not found")?,
+            ErrorKind::RateLimited { retry_after } => {
+                write!(f, "rate limited, retry after {retry_after:?}")?;
+            },
+            ErrorKind::Network => write!(f, "network error")?,
+            ErrorKind::Other => write!(f, "platform error")?,
+        }
+
+        if let Some(code) = self.code {
+            write!(f, ", code: `{code}`")?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, ", description: `{description}`")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
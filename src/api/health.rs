@@ -0,0 +1,190 @@
+use std::fmt::{Debug, Display};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::Sender;
+
+use crate::Event;
+use crate::api::BotAPI;
+
+/// Consecutive failures after which a degraded API is considered down.
+static DOWN_AFTER_FAILURES: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Connecting,
+    Connected,
+    /// Recent attempts to reach the platform failed, but it is not given up
+    /// on yet.
+    Degraded,
+    Down,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connecting => write!(f, "connecting"),
+            Self::Connected => write!(f, "connected"),
+            Self::Degraded => write!(f, "degraded"),
+            Self::Down => write!(f, "down"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Health {
+    status: Status,
+    since: DateTime<Utc>,
+    last_error: Option<String>,
+    last_event: Option<DateTime<Utc>>,
+}
+
+impl Health {
+    #[must_use]
+    pub fn new(status: Status) -> Self {
+        Self {
+            status,
+            since: Utc::now(),
+            last_error: None,
+            last_event: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn get_status(&self) -> Status {
+        self.status
+    }
+
+    /// When the current status was entered.
+    #[must_use]
+    pub const fn get_since(&self) -> DateTime<Utc> {
+        self.since
+    }
+
+    #[must_use]
+    pub fn get_last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    #[must_use]
+    pub const fn get_last_event(&self) -> Option<DateTime<Utc>> {
+        self.last_event
+    }
+}
+
+impl Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} since {}", self.status, self.since)?;
+
+        if let Some(last_event) = self.last_event {
+            write!(f, ", last event at {last_event}")?;
+        }
+        if let Some(last_error) = &self.last_error {
+            write!(f, ", last error: {last_error}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps the [`Health`] of an adapter up to date from what its connection
+/// loop reports.
+pub struct HealthTracker {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    health: Health,
+    failures: u32,
+}
+
+impl HealthTracker {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                health: Health::new(Status::Connecting),
+                failures: 0,
+            }),
+        }
+    }
+
+    pub fn get(&self) -> Health {
+        self.lock().health.clone()
+    }
+
+    /// Returns the previous status if it changed.
+    pub fn succeeded(&self) -> Option<Status> {
+        let mut inner = self.lock();
+
+        inner.failures = 0;
+        inner.set_status(Status::Connected)
+    }
+
+    /// Returns the previous status if it changed.
+    pub fn failed(&self, err: &anyhow::Error) -> Option<Status> {
+        let mut inner = self.lock();
+
+        inner.failures += 1;
+        inner.health.last_error = Some(format!("{err:#}"));
+
+        let status = if inner.failures < DOWN_AFTER_FAILURES {
+            Status::Degraded
+        } else {
+            Status::Down
+        };
+        inner.set_status(status)
+    }
+
+    pub fn event_received(&self) {
+        self.lock().health.last_event = Some(Utc::now());
+    }
+
+    /// Delivers [`Event::ApiStatusChanged`] if `previous` is a change
+    /// returned by the other methods.
+    pub async fn report<C>(
+        &self,
+        api: Arc<dyn BotAPI<C>>,
+        event_tx: &Sender<Event<C>>,
+        previous: Option<Status>,
+    ) where
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        let Some(previous) = previous else {
+            return;
+        };
+
+        let health = self.get();
+        tracing::warn!("{} api is now {health}, was {previous}", api.get_platform());
+
+        if let Err(err) = event_tx
+            .send(Event::ApiStatusChanged {
+                api,
+                previous,
+                health,
+            })
+            .await
+        {
+            tracing::error!("{err:?}");
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl Inner {
+    fn set_status(&mut self, status: Status) -> Option<Status> {
+        let previous = self.health.status;
+        if previous == status {
+            return None;
+        }
+
+        self.health.status = status;
+        self.health.since = Utc::now();
+
+        Some(previous)
+    }
+}
@@ -4,7 +4,7 @@ use std::time::Duration;
 
 use anyhow::Result;
 use tokio::sync::Mutex;
-use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
 use crate::BotAPI;
 
@@ -16,8 +16,8 @@ pub struct Mock<C>
 where
     C: Clone + Debug + Send + Sync + 'static,
 {
-    event_tx: Sender<crate::Event<C>>,
-    event_rx: Arc<Mutex<Receiver<crate::Event<C>>>>,
+    event_tx: UnboundedSender<crate::Event<C>>,
+    event_rx: Arc<Mutex<UnboundedReceiver<crate::Event<C>>>>,
 
     actions: Arc<Mutex<Vec<Action<C>>>>,
 
@@ -35,7 +35,8 @@ where
     where
         C: Clone + Debug + Send + Sync + 'static,
     {
-        let (event_tx, event_rx) = tokio::sync::mpsc::channel::<crate::Event<C>>(1);
+        // Unbounded so that `happen` keeps the order of events
+        let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<crate::Event<C>>();
 
         Self {
             self_user: crate::User::new(DEFAULT_BOT_ID.to_owned()),
@@ -49,13 +50,16 @@ where
         }
     }
 
+    /// Sets the user the bot is, e.g. to give it a username.
+    #[must_use]
+    pub fn self_user(self, self_user: crate::User) -> Self {
+        Self { self_user, ..self }
+    }
+
     pub fn happen(self: &Arc<Self>, event: crate::Event<C>) {
-        let self_clone = self.clone();
-        tokio::spawn(async move {
-            if let Err(err) = self_clone.event_tx.send(event).await {
-                tracing::error!("{err:?}");
-            }
-        });
+        if let Err(err) = self.event_tx.send(event) {
+            tracing::error!("{err:?}");
+        }
     }
 
     pub fn simple_msg(self: &Arc<Self>, contents: crate::MessageContents) {
@@ -100,6 +104,10 @@ where
         &self.self_user
     }
 
+    fn get_platform(&self) -> &'static str {
+        "mock"
+    }
+
     async fn run(self: Arc<Self>) {}
 
     async fn next_event(&self) -> Option<crate::Event<C>> {
@@ -111,8 +119,8 @@ where
         &self,
         contents: crate::MessageContents,
         chat: crate::Chat<C>,
-        _: Option<&crate::Message<C>>,
-    ) -> Result<String> {
+        _: Option<&str>,
+    ) -> Result<Vec<String>> {
         self.actions
             .lock()
             .await
@@ -123,7 +131,7 @@ where
                 self.self_user.clone(),
             )));
 
-        Ok(DEFAULT_SENDER_ID.to_owned())
+        Ok(vec![DEFAULT_SENDER_ID.to_owned()])
     }
 
     async fn is_group_admin(&self, user: &crate::User, _: &crate::Group) -> Result<bool> {
@@ -1,15 +1,22 @@
 use std::fmt::Debug;
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
-use crate::{Chat, Event, Group, Message, MessageContents, User};
+use crate::{Chat, Event, Group, MediaSource, Message, MessageContents, User};
 
 pub mod cli;
+mod error;
+mod health;
 pub mod mock;
 pub mod onebot_11;
+pub mod replay;
+pub mod send_queue;
 pub mod telegram;
 
+pub use error::{Error, ErrorKind};
+pub use health::{Health, Status};
+
 #[async_trait::async_trait]
 pub trait BotAPI<C>: Send + Sync + 'static
 where
@@ -17,15 +24,20 @@ where
 {
     fn get_context(&self) -> &C;
     fn get_self_user(&self) -> &User;
+    /// Identifies the platform, so that users of different APIs on the same
+    /// platform can be recognized as the same people.
+    fn get_platform(&self) -> &'static str;
 
     async fn run(self: Arc<Self>);
 
     async fn next_event(&self) -> Option<Event<C>>;
 
+    /// Fails if nothing sent has a message id, e.g. only files uploaded with
+    /// [`OneBot11`](onebot_11::OneBot11), as there is no message to reply to.
     async fn send_msg(&self, contents: MessageContents, chat: Chat<C>) -> Result<String> {
         tracing::info!("sending message to [{chat:?}]: {contents}");
 
-        self.send_msg_inner(contents, chat, None).await
+        first(self.send_parts(contents, chat, None).await?)
     }
     async fn reply_to_msg(
         &self,
@@ -34,19 +46,74 @@ where
     ) -> Result<String> {
         tracing::info!("replying to message [{reply_to_message:?}]: {contents}");
 
-        self.send_msg_inner(
-            contents,
-            reply_to_message.get_chat().clone(),
-            Some(reply_to_message),
+        first(
+            self.send_parts(
+                contents,
+                reply_to_message.get_chat().clone(),
+                Some(reply_to_message.get_id()),
+            )
+            .await?,
         )
-        .await
     }
+    /// Sends `contents` in parts of at most [`BotAPI::get_max_length`], in
+    /// order, and returns the ids of all the messages sent. Only the first
+    /// part replies to the message of `reply_to_id` in `chat`.
+    async fn send_parts(
+        &self,
+        contents: MessageContents,
+        chat: Chat<C>,
+        mut reply_to_id: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let parts = match self.get_max_length() {
+            Some(max_length) => contents.split(max_length),
+            None => vec![contents],
+        };
+
+        let mut ids = Vec::new();
+        for part in parts {
+            let res = self
+                .send_msg_inner(part, chat.clone(), reply_to_id.take())
+                .await;
+            crate::metrics::message_sent(&crate::metrics::api_label(self), res.is_ok());
+            ids.extend(res?);
+        }
+
+        Ok(ids)
+    }
+    /// Returns the ids of all the messages sent, as a platform may need more
+    /// than one for `contents`, or none if what is sent has no id.
     async fn send_msg_inner(
         &self,
         contents: MessageContents,
         chat: Chat<C>,
-        reply_to_message: Option<&Message<C>>,
-    ) -> Result<String>;
+        reply_to_id: Option<&str>,
+    ) -> Result<Vec<String>>;
 
     async fn is_group_admin(&self, user: &User, group: &Group) -> Result<bool>;
+
+    /// Reads the data of a media received by this API.
+    ///
+    /// Adapters that receive [`MediaSource::Id`]s resolve them here.
+    async fn download(&self, source: &MediaSource) -> Result<Vec<u8>> {
+        source.read().await
+    }
+
+    /// The longest message the platform accepts, in UTF-16 code units.
+    /// Longer messages are split, see [`MessageContents::split`]. No limit by
+    /// default.
+    fn get_max_length(&self) -> Option<usize> {
+        None
+    }
+
+    /// Adapters without a connection to watch are always connected.
+    fn get_health(&self) -> Health {
+        Health::new(Status::Connected)
+    }
+}
+
+/// The id of a message sent in parts is that of the first part.
+fn first(ids: Vec<String>) -> Result<String> {
+    ids.into_iter()
+        .next()
+        .context("the message is sent, but no message id is returned")
 }
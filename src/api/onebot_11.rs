@@ -1,15 +1,26 @@
 use std::fmt::Debug;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Error, Result};
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::task::JoinHandle;
 use url::Url;
 
-use crate::BotAPI;
+use crate::api::health::HealthTracker;
+use crate::api::send_queue::{self, RateLimit, SendQueue};
+use crate::api::{Error, ErrorKind, Health};
+use crate::{BotAPI, metrics};
+
+/// How many events may be being converted at once.
+const PENDING_EVENTS: usize = 16;
+
+type Pending<C> = JoinHandle<Result<Option<crate::Event<C>>>>;
 
 pub struct OneBot11<C>
 where
@@ -23,6 +34,10 @@ where
 
     self_user: crate::User,
 
+    send_queue: SendQueue,
+    health: HealthTracker,
+    read_local_files: bool,
+
     context: C,
 }
 
@@ -61,13 +76,38 @@ where
 
             self_user: crate::User::new(resp.user_id.to_string()).nickname(resp.nickname),
 
+            send_queue: SendQueue::new(RateLimit::unlimited()),
+            health: HealthTracker::new(),
+            read_local_files: false,
+
             context,
         })
     }
 
+    #[must_use]
+    pub fn rate_limit(self, rate_limit: RateLimit) -> Self {
+        Self {
+            send_queue: SendQueue::new(rate_limit),
+            ..self
+        }
+    }
+
+    /// Lets [`BotAPI::download`] read the paths of files the implementation
+    /// returns from this host. Only for an implementation on the same host
+    /// that is trusted, as it can name any file. Off by default, when only
+    /// urls and data are accepted.
+    #[must_use]
+    pub fn read_local_files(self, read_local_files: bool) -> Self {
+        Self {
+            read_local_files,
+            ..self
+        }
+    }
+
     async fn handle_ws_msg(
         self: &Arc<Self>,
         msg: tungstenite::Result<tungstenite::Message>,
+        pending: &Sender<Pending<C>>,
     ) -> Result<()> {
         let msg_debug = format!("{msg:?}");
 
@@ -75,34 +115,76 @@ where
             let event: Event = serde_json::from_str(&text)
                 .with_context(|| format!("failed to decode json from `{text}`"))?;
 
-            let Event::Message {
+            let self_clone = self.clone();
+            pending
+                .send(tokio::spawn(async move {
+                    self_clone.convert_event(event, &text).await
+                }))
+                .await
+                .context("events are no longer passed on")?;
+        } else {
+            anyhow::bail!("`{msg_debug} is not a text");
+        }
+
+        Ok(())
+    }
+
+    /// Passes the events on in the order they are received, each once it is
+    /// converted.
+    async fn pass_events(self: Arc<Self>, mut pending: Receiver<Pending<C>>) {
+        while let Some(task) = pending.recv().await {
+            let event = match task.await {
+                Ok(Ok(Some(event))) => event,
+                Ok(Ok(None)) => continue,
+                Ok(Err(err)) => {
+                    tracing::error!("{err:?}");
+                    continue;
+                },
+                Err(err) => {
+                    tracing::error!("failed to convert event: {err:?}");
+                    continue;
+                },
+            };
+
+            self.health.event_received();
+            if self.event_tx.send(event).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn convert_event(
+        self: &Arc<Self>,
+        event: Event,
+        raw: &str,
+    ) -> Result<Option<crate::Event<C>>> {
+        Ok(Some(match event {
+            Event::Message {
                 message_id,
                 message_type,
                 group_id,
                 user_id,
                 message,
                 sender,
-            } = event
-            else {
-                return Ok(());
-            };
+            } => {
+                let reply_to = message.iter().find_map(|msg| match msg {
+                    MessageSegment::Reply { id } => Some(id.clone()),
+                    _ => None,
+                });
 
-            let mut contents = crate::MessageContents::new();
+                let forward = message.iter().find_map(|msg| match msg {
+                    MessageSegment::Forward { id } => Some(id.clone()),
+                    _ => None,
+                });
 
-            for msg in message {
-                match msg {
-                    MessageSegment::Text { text } => contents = contents.text(text),
-                    MessageSegment::At { qq } => {
-                        contents = contents.at(crate::User::new(qq));
-                    },
-                    _ => (),
+                let mut contents = convert_segments(message);
+                if let Some(id) = forward {
+                    contents = contents.forward(self.convert_forward(&id).await);
                 }
-            }
 
-            let sender = crate::User::new(user_id.to_string()).nickname(sender.nickname);
+                let sender = crate::User::new(user_id.to_string()).nickname(sender.nickname);
 
-            self.event_tx
-                .send(crate::Event::Message(crate::Message::new(
+                let msg = crate::Message::new(
                     message_id.to_string(),
                     contents,
                     match message_type {
@@ -113,12 +195,375 @@ where
                         ),
                     },
                     sender,
-                )))
-                .await?;
-        } else {
-            anyhow::bail!("`{msg_debug} is not a text");
+                );
+
+                crate::Event::Message(match reply_to {
+                    Some(id) => msg.reply_to(self.convert_reply_to(id).await),
+                    None => msg,
+                })
+            },
+            Event::Notice(notice) => self.convert_notice(notice, raw).await?,
+            Event::Request(request) => self.convert_request(request, raw),
+            Event::Meta {} => return Ok(None),
+            Event::Other => crate::Event::Other(raw.to_owned()),
+        }))
+    }
+
+    async fn convert_notice(
+        self: &Arc<Self>,
+        notice: Notice,
+        raw: &str,
+    ) -> Result<crate::Event<C>> {
+        Ok(match notice {
+            Notice::GroupIncrease {
+                group_id,
+                user_id,
+                operator_id,
+            } => {
+                let chat = self.group_chat(group_id);
+                let operator = to_user(operator_id);
+
+                if user_id.to_string() == self.self_user.id {
+                    crate::Event::BotAdded { chat, operator }
+                } else {
+                    crate::Event::MemberJoined {
+                        chat,
+                        user: crate::User::new(user_id.to_string()),
+                        operator,
+                    }
+                }
+            },
+            Notice::GroupDecrease {
+                sub_type,
+                group_id,
+                user_id,
+                operator_id,
+            } => {
+                let chat = self.group_chat(group_id);
+                let operator = to_user(operator_id);
+
+                if sub_type == "kick_me" || user_id.to_string() == self.self_user.id {
+                    crate::Event::BotRemoved { chat, operator }
+                } else {
+                    crate::Event::MemberLeft {
+                        chat,
+                        user: crate::User::new(user_id.to_string()),
+                        operator,
+                    }
+                }
+            },
+            Notice::GroupRecall {
+                group_id,
+                user_id,
+                operator_id,
+                message_id,
+            } => crate::Event::MessageRecalled {
+                chat: self.group_chat(group_id),
+                message_id: message_id.to_string(),
+                sender: to_user(user_id),
+                operator: to_user(operator_id),
+            },
+            Notice::FriendRecall {
+                user_id,
+                message_id,
+            } => {
+                let user = crate::User::new(user_id.to_string());
+
+                crate::Event::MessageRecalled {
+                    chat: crate::Chat::private(self.clone(), user.clone()),
+                    message_id: message_id.to_string(),
+                    sender: Some(user.clone()),
+                    operator: Some(user),
+                }
+            },
+            Notice::Notify {
+                sub_type,
+                group_id,
+                user_id,
+                target_id,
+            } if sub_type == "poke" => {
+                let sender = crate::User::new(user_id.to_string());
+
+                crate::Event::Poke {
+                    chat: group_id.map_or_else(
+                        || crate::Chat::private(self.clone(), sender.clone()),
+                        |group_id| self.group_chat(group_id),
+                    ),
+                    sender,
+                    target: crate::User::new(target_id.context("no target id")?.to_string()),
+                }
+            },
+            // Files are sent to groups by uploading rather than in messages
+            Notice::GroupUpload {
+                group_id,
+                user_id,
+                file,
+            } => self.convert_group_upload(group_id, user_id, file).await,
+            Notice::Notify { .. } | Notice::Other => crate::Event::Other(raw.to_owned()),
+        })
+    }
+
+    async fn convert_group_upload(
+        self: &Arc<Self>,
+        group_id: i64,
+        user_id: i64,
+        file: UploadedFile,
+    ) -> crate::Event<C> {
+        let url = match self
+            .call_api::<_, GetGroupFileUrlData>(
+                "get_group_file_url",
+                reqwest::Method::POST,
+                Some(GetGroupFileUrlReq {
+                    group_id,
+                    file_id: file.id.clone(),
+                    busid: file.busid,
+                }),
+            )
+            .await
+        {
+            Ok(data) => Some(data.url),
+            Err(err) => {
+                tracing::warn!("{err:?}");
+                None
+            },
+        };
+
+        crate::Event::Message(crate::Message::new(
+            file.id.clone(),
+            crate::MessageContents::new()
+                .file(crate::Attachment::new(to_source(file.id, url)).name(file.name)),
+            self.group_chat(group_id),
+            crate::User::new(user_id.to_string()),
+        ))
+    }
+
+    /// Looks the quoted message up, since the reply segment only has its id.
+    async fn convert_reply_to(self: &Arc<Self>, id: String) -> crate::ReplyTo {
+        let data = match id.parse() {
+            Ok(message_id) => {
+                self.call_api::<_, GetMsgData>(
+                    "get_msg",
+                    reqwest::Method::POST,
+                    Some(GetMsgReq { message_id }),
+                )
+                .await
+            },
+            Err(err) => Err(err.into()),
+        };
+
+        match data {
+            Ok(data) => {
+                let reply_to = crate::ReplyTo::new(id).contents(convert_segments(data.message));
+                match to_user(data.sender.user_id) {
+                    Some(sender) => reply_to.sender(sender.nickname(data.sender.nickname)),
+                    None => reply_to,
+                }
+            },
+            Err(err) => {
+                tracing::warn!("failed to get the replied message `{id}`: {err:?}");
+                crate::ReplyTo::new(id)
+            },
+        }
+    }
+
+    /// Looks the nodes up, since the forward segment only has an id.
+    async fn convert_forward(self: &Arc<Self>, id: &str) -> Vec<crate::ForwardNode> {
+        match self
+            .call_api::<_, GetForwardMsgData>(
+                "get_forward_msg",
+                reqwest::Method::POST,
+                Some(GetForwardMsgReq { id: id.to_owned() }),
+            )
+            .await
+        {
+            Ok(data) => data
+                .messages
+                .into_iter()
+                .map(|node| {
+                    crate::ForwardNode::new(
+                        to_user(node.sender.user_id)
+                            .unwrap_or_else(|| crate::User::new(String::new()))
+                            .nickname(node.sender.nickname),
+                        convert_segments(node.content),
+                    )
+                })
+                .collect(),
+            Err(err) => {
+                tracing::warn!("failed to get the forwarded messages `{id}`: {err:?}");
+                Vec::new()
+            },
+        }
+    }
+
+    fn convert_request(self: &Arc<Self>, request: Request, raw: &str) -> crate::Event<C> {
+        match request {
+            Request::Friend {
+                user_id,
+                comment,
+                flag,
+            } => {
+                let user = crate::User::new(user_id.to_string());
+
+                crate::Event::FriendRequest {
+                    chat: crate::Chat::private(self.clone(), user.clone()),
+                    user,
+                    comment: Some(comment),
+                    flag: Some(flag),
+                }
+            },
+            Request::Group {
+                sub_type,
+                group_id,
+                user_id,
+                comment,
+                flag,
+            } if sub_type == "add" => crate::Event::GroupJoinRequest {
+                chat: self.group_chat(group_id),
+                user: crate::User::new(user_id.to_string()),
+                comment: Some(comment),
+                flag: Some(flag),
+            },
+            Request::Group { .. } | Request::Other => crate::Event::Other(raw.to_owned()),
+        }
+    }
+
+    async fn report(self: &Arc<Self>, previous: Option<crate::api::Status>) {
+        self.health
+            .report(self.clone(), &self.event_tx, previous)
+            .await;
+    }
+
+    fn group_chat(self: &Arc<Self>, group_id: i64) -> crate::Chat<C> {
+        crate::Chat::group(self.clone(), crate::Group::new(group_id.to_string()))
+    }
+
+    async fn send_segments(
+        &self,
+        chat: &crate::Chat<C>,
+        mut message: Vec<MessageSegment>,
+        reply_to_id: Option<&str>,
+    ) -> Result<String> {
+        if let Some(reply_to_id) = reply_to_id {
+            message.insert(
+                0,
+                MessageSegment::Reply {
+                    id: reply_to_id.to_owned(),
+                },
+            );
+        }
+
+        let req = match chat.get_info() {
+            crate::ChatInfo::Private(user) => SendMsgReq::Private {
+                user_id: user.id.parse()?,
+                message,
+            },
+            crate::ChatInfo::Group(group) => SendMsgReq::Group {
+                group_id: group.id.parse()?,
+                message,
+            },
+        };
+
+        self.send_queue
+            .send(&send_queue::chat_key(chat), || async {
+                let resp: SendMsgData = self
+                    .call_api("send_msg", reqwest::Method::POST, Some(&req))
+                    .await?;
+
+                Ok(resp.message_id.to_string())
+            })
+            .await
+    }
+
+    async fn send_forward(
+        &self,
+        chat: &crate::Chat<C>,
+        nodes: Vec<crate::ForwardNode>,
+    ) -> Result<String> {
+        let mut messages = Vec::new();
+        for node in nodes {
+            let mut content = Vec::new();
+            for c in node.get_contents().clone() {
+                content.extend(to_segments(c, chat.is_private()).await?);
+            }
+
+            messages.push(MessageSegment::Node {
+                id: None,
+                name: Some(node.get_name().to_owned()),
+                uin: Some(node.get_sender().id.clone()),
+                content: Some(content),
+            });
         }
 
+        let (api, req) = match chat.get_info() {
+            crate::ChatInfo::Private(user) => (
+                "send_private_forward_msg",
+                SendForwardMsgReq::Private {
+                    user_id: user.id.parse()?,
+                    messages,
+                },
+            ),
+            crate::ChatInfo::Group(group) => (
+                "send_group_forward_msg",
+                SendForwardMsgReq::Group {
+                    group_id: group.id.parse()?,
+                    messages,
+                },
+            ),
+        };
+
+        self.send_queue
+            .send(&send_queue::chat_key(chat), || async {
+                let resp: SendMsgData = self
+                    .call_api(api, reqwest::Method::POST, Some(&req))
+                    .await?;
+
+                Ok(resp.message_id.to_string())
+            })
+            .await
+    }
+
+    async fn upload_file(
+        &self,
+        chat: &crate::Chat<C>,
+        attachment: &crate::Attachment,
+    ) -> Result<()> {
+        let file = to_file(attachment.get_source()).await?;
+        let name = match (attachment.get_name(), attachment.get_source()) {
+            (Some(name), _) => name.to_owned(),
+            (None, crate::MediaSource::Path(path)) => path
+                .file_name()
+                .map_or_else(|| "file".to_owned(), |name| name.to_string_lossy().into()),
+            (None, _) => "file".to_owned(),
+        };
+
+        let (api, req) = match chat.get_info() {
+            crate::ChatInfo::Private(user) => (
+                "upload_private_file",
+                UploadFileReq::Private {
+                    user_id: user.id.parse()?,
+                    file,
+                    name,
+                },
+            ),
+            crate::ChatInfo::Group(group) => (
+                "upload_group_file",
+                UploadFileReq::Group {
+                    group_id: group.id.parse()?,
+                    file,
+                    name,
+                },
+            ),
+        };
+
+        self.send_queue
+            .send(&send_queue::chat_key(chat), || async {
+                self.call_api::<_, ()>(api, reqwest::Method::POST, Some(&req))
+                    .await?;
+
+                Ok(String::new())
+            })
+            .await?;
+
         Ok(())
     }
 
@@ -137,7 +582,10 @@ where
             .join(api)
             .with_context(|| format!("failed to join `{}` and {api}", self.api_url))?;
 
-        call_api(url, method, req).await
+        let start = Instant::now();
+        let res = call_api(url, method, req).await;
+        metrics::api_called(&metrics::api_label(self), api, start.elapsed(), res.is_ok());
+        res
     }
 }
 
@@ -154,7 +602,21 @@ where
         &self.self_user
     }
 
+    fn get_platform(&self) -> &'static str {
+        "onebot_11"
+    }
+
+    // No documented limit, but QQ truncates or flags longer messages
+    fn get_max_length(&self) -> Option<usize> {
+        Some(3000)
+    }
+
     async fn run(self: Arc<Self>) {
+        // Converted off the reader, since replies, forwards and uploads are
+        // looked up through the api, and passed on in order
+        let (pending_tx, pending_rx) = tokio::sync::mpsc::channel(PENDING_EVENTS);
+        tokio::spawn(self.clone().pass_events(pending_rx));
+
         loop {
             let (mut ws_stream, _) = match tokio_tungstenite::connect_async(self.event_url.as_str())
                 .await
@@ -163,22 +625,29 @@ where
                 Ok(r) => r,
                 Err(err) => {
                     tracing::error!("{err:?}");
+                    self.report(self.health.failed(&err)).await;
                     tokio::time::sleep(Duration::from_secs(3)).await;
                     continue;
                 },
             };
+            self.report(self.health.succeeded()).await;
 
             while let Some(msg) = ws_stream.next().await {
-                let self_clone = self.clone();
-                tokio::spawn(async move {
-                    if let Err(err) = self_clone.handle_ws_msg(msg).await {
-                        tracing::error!("{err:?}");
-                    }
-                });
+                if let Err(err) = self.handle_ws_msg(msg, &pending_tx).await {
+                    tracing::error!("{err:?}");
+                }
             }
+
+            let err = anyhow::anyhow!("connection to `{}` closed", self.event_url);
+            tracing::error!("{err:?}");
+            self.report(self.health.failed(&err)).await;
         }
     }
 
+    fn get_health(&self) -> Health {
+        self.health.get()
+    }
+
     async fn next_event(&self) -> Option<crate::Event<C>> {
         let mut events = self.event_rx.lock().await;
         events.recv().await
@@ -188,51 +657,96 @@ where
         &self,
         contents: crate::MessageContents,
         chat: crate::Chat<C>,
-        reply_to_msg: Option<&crate::Message<C>>,
-    ) -> Result<String> {
-        let mut message = Vec::new();
-
-        if let Some(reply_to_msg) = reply_to_msg {
-            message.push(MessageSegment::Reply {
-                id: reply_to_msg.id.clone(),
-            });
-        }
-
+        reply_to_id: Option<&str>,
+    ) -> Result<Vec<String>> {
+        // Files and forwards are sent on their own, between the messages of
+        // the contents around them
+        let mut parts = Vec::new();
         for content in contents {
             match content {
-                crate::MessageContent::Text(text) => message.push(MessageSegment::Text { text }),
-                crate::MessageContent::At(user) => {
-                    if chat.is_private() {
-                        message.push(MessageSegment::Text {
-                            text: user.get_nickname().to_string(),
-                        });
-                    } else {
-                        message.push(MessageSegment::At { qq: user.id });
+                crate::MessageContent::File(attachment) => parts.push(Part::File(attachment)),
+                crate::MessageContent::Forward(nodes) => parts.push(Part::Forward(nodes)),
+                content => {
+                    let segments = to_segments(content, chat.is_private()).await?;
+                    match parts.last_mut() {
+                        Some(Part::Message(message)) => message.extend(segments),
+                        _ => parts.push(Part::Message(segments)),
                     }
+                },
+            }
+        }
 
-                    message.push(MessageSegment::Text {
-                        text: " ".to_string(),
-                    });
+        let mut ids = Vec::new();
+        let mut reply_to_id = reply_to_id;
+        for part in parts {
+            // Only messages can quote, so a file or forward that comes first is
+            // preceded by a message that does
+            if let Some(text) = part.get_label() &&
+                reply_to_id.is_some()
+            {
+                let message = vec![MessageSegment::Text { text }];
+                ids.push(
+                    self.send_segments(&chat, message, reply_to_id.take())
+                        .await?,
+                );
+            }
+
+            match part {
+                Part::Message(message) => {
+                    ids.push(
+                        self.send_segments(&chat, message, reply_to_id.take())
+                            .await?,
+                    );
                 },
+                Part::File(attachment) => self.upload_file(&chat, &attachment).await?,
+                Part::Forward(nodes) => ids.push(self.send_forward(&chat, nodes).await?),
             }
         }
 
-        let req = match chat.get_info() {
-            crate::ChatInfo::Private(user) => SendMsgReq::Private {
-                user_id: user.id.parse()?,
-                message,
-            },
-            crate::ChatInfo::Group(group) => SendMsgReq::Group {
-                group_id: group.id.parse()?,
-                message,
-            },
+        // Uploading files yields no message id, so contents of only files have
+        // none at all
+        Ok(ids)
+    }
+
+    async fn download(&self, source: &crate::MediaSource) -> Result<Vec<u8>> {
+        let crate::MediaSource::Id(file) = source else {
+            return source.read().await;
         };
 
-        let resp: SendMsgData = self
-            .call_api("send_msg", reqwest::Method::POST, Some(req))
-            .await?;
+        // The id does not tell the kind of media, so each lookup is tried in
+        // turn, `get_file` being an extension of go-cqhttp and its successors
+        let mut last_err = None;
+        for (api, out_format) in [
+            ("get_image", None),
+            ("get_record", Some("mp3")),
+            ("get_file", None),
+        ] {
+            let res = self
+                .call_api::<_, GetMediaData>(
+                    api,
+                    reqwest::Method::POST,
+                    Some(GetMediaReq {
+                        file: file.clone(),
+                        file_id: file.clone(),
+                        out_format,
+                    }),
+                )
+                .await;
 
-        Ok(resp.message_id.to_string())
+            match res {
+                Ok(data) => {
+                    return data
+                        .read(self.read_local_files)
+                        .await
+                        .with_context(|| format!("failed to download file `{file}`"));
+                },
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err
+            .unwrap_or_else(|| anyhow::anyhow!("no api to look files up"))
+            .context(format!("failed to look file `{file}` up")))
     }
 
     async fn is_group_admin(&self, user: &crate::User, group: &crate::Group) -> Result<bool> {
@@ -262,6 +776,7 @@ where
 
     let resp: Resp<D> = food_http_rs::call_api(url, method, req)
         .await
+        .context(Error::new(ErrorKind::Network, None, None))
         .with_context(|| {
             format!("failed to call api `{url_str}({method_str})`, req: `{req_debug}`")
         })?;
@@ -270,11 +785,30 @@ where
         Ok(data)
     } else {
         if matches!(resp.status, RespStatus::Failed) {
-            anyhow::bail!(
-                "onebot 11 api `{url_str}({method_str})` returns failed, req: `{req_debug}`, retcode: `{}`, error: `{}`",
-                resp.retcode,
-                resp.message
-            );
+            let message = resp.message.to_lowercase();
+            let kind = match resp.retcode {
+                1403 => ErrorKind::Forbidden,
+                // 1404 stands for an unknown api rather than a missing chat
+                1404 => ErrorKind::Other,
+                _ if message.contains("not found") || message.contains("not_found") => {
+                    ErrorKind::ChatNotFound
+                },
+                _ => ErrorKind::Other,
+            };
+
+            return Err(anyhow::Error::new(Error::new(
+                kind,
+                Some(resp.retcode.into()),
+                Some(resp.message),
+            ))
+            .context(format!(
+                "onebot 11 api `{url_str}({method_str})` returns failed, req: `{req_debug}`"
+            )));
+        }
+
+        // Some apis, like those uploading files, return no data on success
+        if let Ok(data) = serde_json::from_value(serde_json::Value::Null) {
+            return Ok(data);
         }
 
         anyhow::bail!("onebot 11 api `{url_str}({method_str})` returns empty data");
@@ -300,26 +834,76 @@ enum RespStatus {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data", rename_all = "snake_case")]
 enum MessageSegment {
-    Text { text: String },
-    Face { id: String },
-    Image { file: String },
-    Record { file: String },
-    Video { file: String },
-    At { qq: String },
+    Text {
+        text: String,
+    },
+    Face {
+        id: String,
+    },
+    Image {
+        file: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    Record {
+        file: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    Video {
+        file: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    At {
+        qq: String,
+    },
     Rps {},
     Dice {},
     Shake {},
-    Poke { r#type: String, id: String },
+    Poke {
+        r#type: String,
+        id: String,
+    },
     Anonymous {},
-    Share { url: String, title: String },
-    Contact { r#type: String, id: String },
-    Location { lat: String, lon: String },
-    Music { r#type: String },
-    Reply { id: String },
-    Forward { id: String },
-    Node {},
-    Xml { data: String },
-    Json { data: String },
+    Share {
+        url: String,
+        title: String,
+    },
+    Contact {
+        r#type: String,
+        id: String,
+    },
+    Location {
+        lat: String,
+        lon: String,
+    },
+    Music {
+        r#type: String,
+    },
+    Reply {
+        id: String,
+    },
+    Forward {
+        id: String,
+    },
+    /// Either refers to a message by `id` or is made up of the other fields.
+    Node {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uin: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<Vec<Self>>,
+    },
+    Xml {
+        data: String,
+    },
+    Json {
+        data: String,
+    },
 }
 
 #[derive(Debug, Deserialize)]
@@ -333,10 +917,182 @@ enum Event {
         message: Vec<MessageSegment>,
         sender: MessageSender,
     },
+    Notice(Notice),
+    Request(Request),
+    #[serde(rename = "meta_event")]
+    Meta {},
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "notice_type", rename_all = "snake_case")]
+enum Notice {
+    GroupIncrease {
+        group_id: i64,
+        user_id: i64,
+        operator_id: i64,
+    },
+    GroupDecrease {
+        sub_type: String,
+        group_id: i64,
+        user_id: i64,
+        operator_id: i64,
+    },
+    GroupRecall {
+        group_id: i64,
+        user_id: i64,
+        operator_id: i64,
+        message_id: i64,
+    },
+    FriendRecall {
+        user_id: i64,
+        message_id: i64,
+    },
+    Notify {
+        sub_type: String,
+        group_id: Option<i64>,
+        user_id: i64,
+        target_id: Option<i64>,
+    },
+    GroupUpload {
+        group_id: i64,
+        user_id: i64,
+        file: UploadedFile,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// A run of contents sent at once.
+enum Part {
+    Message(Vec<MessageSegment>),
+    File(crate::Attachment),
+    Forward(Vec<crate::ForwardNode>),
+}
+
+impl Part {
+    /// What a file or forward is called in a message.
+    fn get_label(&self) -> Option<String> {
+        match self {
+            Self::Message(_) => None,
+            Self::File(attachment) => Some(format!("[file: {attachment}]")),
+            Self::Forward(_) => Some("[forward]".to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadedFile {
+    id: String,
+    name: String,
+    busid: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "request_type", rename_all = "snake_case")]
+enum Request {
+    Friend {
+        user_id: i64,
+        comment: String,
+        flag: String,
+    },
+    Group {
+        sub_type: String,
+        group_id: i64,
+        user_id: i64,
+        comment: String,
+        flag: String,
+    },
     #[serde(other)]
     Other,
 }
 
+/// Local data is sent inline, since the implementation may not share the file
+/// system of the bot.
+async fn to_file(source: &crate::MediaSource) -> Result<String> {
+    Ok(match source {
+        crate::MediaSource::Url(url) => url.clone(),
+        crate::MediaSource::Path(_) | crate::MediaSource::Bytes(_) => {
+            format!("base64://{}", BASE64_STANDARD.encode(source.read().await?))
+        },
+        crate::MediaSource::Id(id) => id.clone(),
+    })
+}
+
+/// Converts a content that fits in a message. Files and forwards are sent on
+/// their own, they are only described here.
+async fn to_segments(content: crate::MessageContent, private: bool) -> Result<Vec<MessageSegment>> {
+    Ok(match content {
+        crate::MessageContent::Text(text) => vec![MessageSegment::Text { text }],
+        // No formatting in OneBot 11
+        content @ (crate::MessageContent::Styled { .. } |
+        crate::MessageContent::File(_) |
+        crate::MessageContent::Forward(_)) => vec![MessageSegment::Text {
+            text: content.to_string(),
+        }],
+        crate::MessageContent::Image(source) => vec![MessageSegment::Image {
+            file: to_file(&source).await?,
+            url: None,
+        }],
+        crate::MessageContent::Voice(attachment) => vec![MessageSegment::Record {
+            file: to_file(attachment.get_source()).await?,
+            url: None,
+        }],
+        crate::MessageContent::Video(attachment) => vec![MessageSegment::Video {
+            file: to_file(attachment.get_source()).await?,
+            url: None,
+        }],
+        crate::MessageContent::At(user) => vec![
+            if private {
+                MessageSegment::Text {
+                    text: user.get_nickname().to_string(),
+                }
+            } else {
+                MessageSegment::At { qq: user.id }
+            },
+            MessageSegment::Text {
+                text: " ".to_string(),
+            },
+        ],
+    })
+}
+
+fn convert_segments(segments: Vec<MessageSegment>) -> crate::MessageContents {
+    let mut contents = crate::MessageContents::new();
+
+    for segment in segments {
+        match segment {
+            MessageSegment::Text { text } => contents = contents.text(text),
+            MessageSegment::At { qq } => {
+                contents = contents.at(crate::User::new(qq));
+            },
+            MessageSegment::Image { file, url } => {
+                contents = contents.image(to_source(file, url));
+            },
+            MessageSegment::Record { file, url } => {
+                contents = contents.voice(crate::Attachment::new(to_source(file, url)));
+            },
+            MessageSegment::Video { file, url } => {
+                contents = contents.video(crate::Attachment::new(to_source(file, url)));
+            },
+            _ => (),
+        }
+    }
+
+    contents
+}
+
+/// Received media come with a url when the implementation knows one.
+fn to_source(file: String, url: Option<String>) -> crate::MediaSource {
+    url.map_or(crate::MediaSource::Id(file), crate::MediaSource::Url)
+}
+
+// `0` stands for no user
+fn to_user(user_id: i64) -> Option<crate::User> {
+    (user_id != 0).then(|| crate::User::new(user_id.to_string()))
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
 enum MessageType {
@@ -346,11 +1102,13 @@ enum MessageType {
 
 #[derive(Debug, Deserialize)]
 struct MessageSender {
+    #[serde(default)]
+    user_id: i64,
     nickname: String,
 }
 
 impl TryFrom<&str> for Event {
-    type Error = Error;
+    type Error = anyhow::Error;
 
     fn try_from(value: &str) -> Result<Self> {
         serde_json::from_str(value).with_context(|| format!("failed to deserialize json `{value}`"))
@@ -370,11 +1128,124 @@ enum SendMsgReq {
     },
 }
 
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum SendForwardMsgReq {
+    Private {
+        user_id: i64,
+        messages: Vec<MessageSegment>,
+    },
+    Group {
+        group_id: i64,
+        messages: Vec<MessageSegment>,
+    },
+}
+
 #[derive(Debug, Deserialize)]
 struct SendMsgData {
     message_id: i64,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum UploadFileReq {
+    Private {
+        user_id: i64,
+        file: String,
+        name: String,
+    },
+    Group {
+        group_id: i64,
+        file: String,
+        name: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct GetGroupFileUrlReq {
+    group_id: i64,
+    file_id: String,
+    busid: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetGroupFileUrlData {
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+struct GetMsgReq {
+    message_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetMsgData {
+    sender: MessageSender,
+    message: Vec<MessageSegment>,
+}
+
+#[derive(Debug, Serialize)]
+struct GetForwardMsgReq {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetForwardMsgData {
+    messages: Vec<ForwardMsg>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForwardMsg {
+    sender: MessageSender,
+    // go-cqhttp calls it `content`, most other implementations `message`
+    #[serde(alias = "message")]
+    content: Vec<MessageSegment>,
+}
+
+/// For `get_image`, `get_record` and `get_file`, which name the id differently.
+#[derive(Debug, Serialize)]
+struct GetMediaReq {
+    file: String,
+    file_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    out_format: Option<&'static str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetMediaData {
+    #[serde(default)]
+    file: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    base64: Option<String>,
+}
+
+impl GetMediaData {
+    /// The file is a path on the host of the implementation, so the data or a
+    /// url is preferred where given, and the path is only read if
+    /// `read_local_files`.
+    async fn read(self, read_local_files: bool) -> Result<Vec<u8>> {
+        if let Some(base64) = self.base64 {
+            return BASE64_STANDARD
+                .decode(base64)
+                .context("failed to decode base64 data");
+        }
+
+        match (self.url, self.file) {
+            (Some(url), _) if url.starts_with("http") => crate::MediaSource::Url(url).read().await,
+            (_, Some(file)) => match file.strip_prefix("base64://") {
+                Some(base64) => BASE64_STANDARD
+                    .decode(base64)
+                    .context("failed to decode base64 data"),
+                None if read_local_files => crate::MediaSource::Path(file.into()).read().await,
+                None => anyhow::bail!("only the local path `{file}` is returned"),
+            },
+            _ => anyhow::bail!("neither a file nor a url is returned"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct GetGroupMemberInfoReq {
     group_id: i64,
@@ -387,6 +1258,7 @@ struct GetGroupMemberInfoData {
 }
 
 #[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum GroupMemberInfoRole {
     Owner,
     Admin,
@@ -0,0 +1,232 @@
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio_util::sync::CancellationToken;
+
+use super::mock::Action;
+use crate::BotAPI;
+use crate::recorder::Record;
+
+/// Feeds the events recorded by a [`Recorder`](crate::recorder::Recorder)
+/// back to the bot, and captures what it sends like
+/// [`Mock`](super::mock::Mock).
+///
+/// The records of one API are replayed through this one, which takes the
+/// identity of the bot and the platform from them.
+pub struct Replay<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    records: Vec<Record>,
+    timing: bool,
+
+    event_tx: UnboundedSender<crate::Event<C>>,
+    event_rx: Arc<Mutex<UnboundedReceiver<crate::Event<C>>>>,
+    finished: CancellationToken,
+
+    actions: Arc<Mutex<Vec<Action<C>>>>,
+
+    self_user: crate::User,
+    platform: &'static str,
+
+    context: C,
+}
+
+impl<C> Replay<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    /// Reads the records of `path`, which must all be of the same API.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the records are of more than one API, in which case one of
+    /// them is replayed with [`Replay::open_api`].
+    pub fn open<P>(path: P, context: C) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let records = read_records(path)?;
+
+        let first = records
+            .first()
+            .with_context(|| format!("no record in {}", path.display()))?;
+        let platform = first.get_platform().to_owned();
+        let self_id = first.get_self_id().clone();
+
+        if let Some(other) = records
+            .iter()
+            .find(|record| record.get_platform() != platform || *record.get_self_id() != self_id)
+        {
+            anyhow::bail!(
+                "{} has records of both `{platform}:{self_id}` and `{}:{}`, replay one of them \
+                 with `Replay::open_api`",
+                path.display(),
+                other.get_platform(),
+                other.get_self_id()
+            );
+        }
+
+        Ok(Self::new(records, &platform, self_id, context))
+    }
+
+    /// Reads the records of `path` received by the API of `platform` whose
+    /// bot is `self_id`, leaving out those of the other APIs.
+    ///
+    /// # Errors
+    pub fn open_api<P>(path: P, platform: &str, self_id: &str, context: C) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let records: Vec<_> = read_records(path)?
+            .into_iter()
+            .filter(|record| record.get_platform() == platform && record.get_self_id() == self_id)
+            .collect();
+        anyhow::ensure!(
+            !records.is_empty(),
+            "no record of `{platform}:{self_id}` in {}",
+            path.display()
+        );
+
+        Ok(Self::new(records, platform, self_id.to_owned(), context))
+    }
+
+    fn new(records: Vec<Record>, platform: &str, self_id: String, context: C) -> Self {
+        // Unbounded so that the records keep their order
+        let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<crate::Event<C>>();
+
+        Self {
+            records,
+            timing: false,
+
+            event_tx,
+            event_rx: Arc::new(Mutex::new(event_rx)),
+            finished: CancellationToken::new(),
+
+            actions: Arc::new(Mutex::new(Vec::new())),
+
+            self_user: crate::User::new(self_id),
+            // Leaked once per replay, as `BotAPI::get_platform` is `'static`
+            platform: Box::leak(platform.to_owned().into_boxed_str()),
+
+            context,
+        }
+    }
+
+    /// Waits between events as long as they were apart when recorded, instead
+    /// of feeding them all at once.
+    #[must_use]
+    pub fn with_timing(self) -> Self {
+        Self {
+            timing: true,
+            ..self
+        }
+    }
+
+    /// Waits until all the records are fed to the bot. They may still be
+    /// being handled.
+    pub async fn finished(&self) {
+        self.finished.cancelled().await;
+    }
+
+    pub async fn get_actions(&self) -> Vec<Action<C>> {
+        self.get_actions_with_timeout(Duration::from_millis(200))
+            .await
+    }
+
+    pub async fn get_actions_with_timeout(&self, timeout: Duration) -> Vec<Action<C>> {
+        tokio::time::sleep(timeout).await;
+        self.actions.lock().await.drain(..).collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl<C> BotAPI<C> for Replay<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    fn get_context(&self) -> &C {
+        &self.context
+    }
+
+    fn get_self_user(&self) -> &crate::User {
+        &self.self_user
+    }
+
+    fn get_platform(&self) -> &'static str {
+        self.platform
+    }
+
+    async fn run(self: Arc<Self>) {
+        let api: Arc<dyn BotAPI<C>> = self.clone();
+
+        let mut last_time = None;
+        for record in &self.records {
+            if self.timing &&
+                let Some(last_time) = last_time &&
+                let Ok(delay) = record.get_time().signed_duration_since(last_time).to_std()
+            {
+                tokio::time::sleep(delay).await;
+            }
+            last_time = Some(record.get_time());
+
+            if let Err(err) = self.event_tx.send(record.to_event(&api)) {
+                tracing::error!("{err:?}");
+            }
+        }
+
+        tracing::info!("replayed {} record(s)", self.records.len());
+        self.finished.cancel();
+    }
+
+    async fn next_event(&self) -> Option<crate::Event<C>> {
+        let mut events = self.event_rx.lock().await;
+        events.recv().await
+    }
+
+    async fn send_msg_inner(
+        &self,
+        contents: crate::MessageContents,
+        chat: crate::Chat<C>,
+        _: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let id = uuid::Uuid::new_v4().to_string();
+
+        self.actions
+            .lock()
+            .await
+            .push(Action::SendMessage(crate::Message::new(
+                id.clone(),
+                contents,
+                chat,
+                self.self_user.clone(),
+            )));
+
+        Ok(vec![id])
+    }
+
+    async fn is_group_admin(&self, _: &crate::User, _: &crate::Group) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+fn read_records(path: &Path) -> Result<Vec<Record>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str::<Record>(line)
+                .with_context(|| format!("invalid record at {}:{}", path.display(), i + 1))
+        })
+        .collect()
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::time::Instant;
+
+use crate::api::Error;
+
+static DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Limits how fast an adapter sends messages.
+///
+/// Sends are queued in the order they are made and each one waits for its
+/// turn, so callers still get the id of the message eventually sent.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    per_chat: Option<Duration>,
+    global: Option<Duration>,
+    max_retries: u32,
+}
+
+impl RateLimit {
+    #[must_use]
+    pub const fn unlimited() -> Self {
+        Self {
+            per_chat: None,
+            global: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+
+    /// Allows at most `count` messages to the same chat every `period`.
+    #[must_use]
+    pub fn per_chat(self, count: u32, period: Duration) -> Self {
+        Self {
+            per_chat: Some(period / count.max(1)),
+            ..self
+        }
+    }
+
+    /// Allows at most `count` messages in total every `period`.
+    #[must_use]
+    pub fn global(self, count: u32, period: Duration) -> Self {
+        Self {
+            global: Some(period / count.max(1)),
+            ..self
+        }
+    }
+
+    /// Sets how many times a send rejected with
+    /// [`ErrorKind::RateLimited`](crate::api::ErrorKind::RateLimited) is
+    /// retried.
+    #[must_use]
+    pub const fn max_retries(self, max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..self
+        }
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+pub(crate) struct SendQueue {
+    limit: RateLimit,
+    turns: Mutex<Turns>,
+}
+
+#[derive(Default)]
+struct Turns {
+    global: Option<Instant>,
+    chats: HashMap<String, Instant>,
+}
+
+impl SendQueue {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            turns: Mutex::new(Turns::default()),
+        }
+    }
+
+    /// Calls `send` once it is the turn of `chat_key`, retrying as long as the
+    /// platform answers that it is rate limited.
+    pub(crate) async fn send<F, Fut>(&self, chat_key: &str, send: F) -> Result<String>
+    where
+        F: Fn() -> Fut + Send,
+        Fut: Future<Output = Result<String>> + Send,
+    {
+        let mut retries = 0;
+        loop {
+            tokio::time::sleep_until(self.take_turn(chat_key)).await;
+
+            match send().await {
+                Err(err) if retries < self.limit.max_retries => {
+                    let Some(retry_after) =
+                        err.downcast_ref::<Error>().and_then(Error::get_retry_after)
+                    else {
+                        return Err(err);
+                    };
+
+                    tracing::warn!("sending to `{chat_key}` is rate limited for {retry_after:?}");
+
+                    self.defer(retry_after);
+                    retries += 1;
+                },
+                res => return res,
+            }
+        }
+    }
+
+    fn take_turn(&self, chat_key: &str) -> Instant {
+        let now = Instant::now();
+        let mut turns = self.lock();
+
+        turns.chats.retain(|_, next| *next > now);
+
+        // The global slot only moves by the global interval, so a busy chat
+        // does not hold up the others.
+        let global_turn = turns.global.map_or(now, |global| global.max(now));
+        let chat_turn = turns
+            .chats
+            .get(chat_key)
+            .map_or(now, |chat| (*chat).max(now));
+        let turn = global_turn.max(chat_turn);
+
+        if let Some(interval) = self.limit.global {
+            turns.global = Some(global_turn + interval);
+        }
+        if let Some(interval) = self.limit.per_chat {
+            turns.chats.insert(chat_key.to_owned(), turn + interval);
+        }
+
+        turn
+    }
+
+    fn defer(&self, retry_after: Duration) {
+        let until = Instant::now() + retry_after;
+        let mut turns = self.lock();
+
+        turns.global = Some(turns.global.map_or(until, |global| global.max(until)));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Turns> {
+        self.turns
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+pub(crate) fn chat_key<C>(chat: &crate::Chat<C>) -> String
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    format!("{}:{}", chat.type_as_i32(), chat.get_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busy_chat_does_not_hold_up_others() {
+        let queue = SendQueue::new(
+            RateLimit::unlimited()
+                .per_chat(1, Duration::from_secs(10))
+                .global(10, Duration::from_secs(1)),
+        );
+        let start = Instant::now();
+
+        let turns: Vec<_> = (0..10).map(|_| queue.take_turn("a")).collect();
+        assert!(
+            turns
+                .windows(2)
+                .all(|turns| turns[1] - turns[0] >= Duration::from_secs(10))
+        );
+
+        let turn = queue.take_turn("b");
+        assert!(turn - start < Duration::from_secs(2), "{:?}", turn - start);
+        assert!(queue.take_turn("c") - turn >= Duration::from_millis(100));
+    }
+}
@@ -1,15 +1,22 @@
 use std::fmt::Debug;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use reqwest::Method;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 use tokio::sync::mpsc::{Receiver, Sender};
 use url::Url;
 
-use crate::BotAPI;
+use crate::api::health::HealthTracker;
+use crate::api::send_queue::{self, RateLimit, SendQueue};
+use crate::api::{Error, ErrorKind, Health};
+use crate::{BotAPI, metrics};
+
+/// Captions are limited more than the 4096 of messages.
+const MAX_CAPTION_LENGTH: usize = 1024;
 
 pub struct Telegram<C>
 where
@@ -23,6 +30,9 @@ where
 
     self_user: crate::User,
 
+    send_queue: SendQueue,
+    health: HealthTracker,
+
     context: C,
 }
 
@@ -57,90 +67,243 @@ where
 
             self_user,
 
+            // https://core.telegram.org/bots/faq#my-bot-is-hitting-limits-how-do-i-avoid-this
+            send_queue: SendQueue::new(
+                RateLimit::unlimited()
+                    .per_chat(1, Duration::from_secs(1))
+                    .global(30, Duration::from_secs(1)),
+            ),
+            health: HealthTracker::new(),
+
             context,
         })
     }
 
+    #[must_use]
+    pub fn rate_limit(self, rate_limit: RateLimit) -> Self {
+        Self {
+            send_queue: SendQueue::new(rate_limit),
+            ..self
+        }
+    }
+
     async fn handle_update(self: &Arc<Self>, update: Update) -> Result<()> {
-        let Some(message) = update.message else {
-            // Ignore updates that are not messages
-            return Ok(());
-        };
+        let mut events = Vec::new();
+
+        if let Some(message) = update.message {
+            let chat = self.convert_chat(message.chat.as_ref());
+            let operator = message.from.as_ref().map(crate::User::from);
+
+            for member in message.new_chat_members.iter().flatten() {
+                // Changes of the bot itself are reported by `my_chat_member`
+                if member.id.to_string() != self.self_user.id {
+                    events.push(crate::Event::MemberJoined {
+                        chat: chat.clone(),
+                        user: member.into(),
+                        operator: operator.clone(),
+                    });
+                }
+            }
 
-        let Some(text) = message.text else {
-            // Ignore messages that do not have text
-            return Ok(());
-        };
+            if let Some(member) = &message.left_chat_member &&
+                member.id.to_string() != self.self_user.id
+            {
+                events.push(crate::Event::MemberLeft {
+                    chat,
+                    user: member.into(),
+                    operator,
+                });
+            }
+
+            if let Some(msg) = self.convert_message(message)? {
+                events.push(crate::Event::Message(msg));
+            }
+        } else if let Some(message) = update.edited_message {
+            if let Some(msg) = self.convert_message(message)? {
+                events.push(crate::Event::MessageEdited(msg));
+            }
+        } else if let Some(updated) = update.my_chat_member {
+            let chat = self.convert_chat(Some(&updated.chat));
+            let operator = Some(crate::User::from(&updated.from));
+
+            if updated.new_chat_member.status.is_present() &&
+                !updated.old_chat_member.status.is_present()
+            {
+                events.push(crate::Event::BotAdded { chat, operator });
+            } else if !updated.new_chat_member.status.is_present() &&
+                updated.old_chat_member.status.is_present()
+            {
+                events.push(crate::Event::BotRemoved { chat, operator });
+            }
+        } else if let Some(request) = update.chat_join_request {
+            events.push(crate::Event::GroupJoinRequest {
+                chat: self.convert_chat(Some(&request.chat)),
+                user: (&request.from).into(),
+                comment: request.bio,
+                flag: None,
+            });
+        } else {
+            // Ignore other updates
+        }
+
+        for event in events {
+            self.health.event_received();
+            self.event_tx.send(event).await?;
+        }
+
+        Ok(())
+    }
+
+    fn convert_message(self: &Arc<Self>, message: Message) -> Result<Option<crate::Message<C>>> {
+        let reply_to = message
+            .reply_to_message
+            .map(|reply_to| self.convert_reply_to(*reply_to))
+            .transpose()?;
+
+        let mut contents = crate::MessageContents::new();
+
+        // The largest size comes last
+        if let Some(photo) = message.photo.as_ref().and_then(|sizes| sizes.last()) {
+            contents = contents.image(crate::MediaSource::Id(photo.file_id.clone()));
+        }
+        if let Some(voice) = message.voice {
+            contents = contents.voice(voice.into());
+        }
+        if let Some(video) = message.video {
+            contents = contents.video(video.into());
+        }
+        // Music is a file rather than a voice, which `sendVoice` would refuse
+        if let Some(document) = message.audio.or(message.document) {
+            contents = contents.file(document.into());
+        }
+
+        // Media come with their text as a caption
+        if let Some(text) = message.text.or(message.caption) {
+            contents = self.convert_text(
+                contents,
+                &text,
+                message.entities.or(message.caption_entities),
+            )?;
+        }
+
+        if contents.is_empty() {
+            // Ignore messages that have neither text nor media
+            return Ok(None);
+        }
+
+        let msg = crate::Message::new(
+            message.message_id.to_string(),
+            contents,
+            self.convert_chat(message.chat.as_ref()),
+            message
+                .from
+                .as_ref()
+                .map_or_else(|| crate::User::new(String::new()), crate::User::from),
+        );
+
+        Ok(Some(match reply_to {
+            Some(reply_to) => msg.reply_to(reply_to),
+            None => msg,
+        }))
+    }
+
+    fn convert_reply_to(self: &Arc<Self>, message: Message) -> Result<crate::ReplyTo> {
+        let id = message.message_id.to_string();
+        let sender = message.from.as_ref().map(crate::User::from);
+
+        // Quoted messages without text or media, such as stickers, only keep
+        // their sender
+        Ok(self.convert_message(message)?.map_or_else(
+            || {
+                let reply_to = crate::ReplyTo::new(id);
+                match sender {
+                    Some(sender) => reply_to.sender(sender),
+                    None => reply_to,
+                }
+            },
+            Into::into,
+        ))
+    }
+
+    fn convert_text(
+        self: &Arc<Self>,
+        mut contents: crate::MessageContents,
+        text: &str,
+        entities: Option<Vec<MessageEntity>>,
+    ) -> Result<crate::MessageContents> {
         let utf16_text: Vec<u16> = text.encode_utf16().collect();
 
-        let contents = if let Some(entities) = message.entities {
-            let mut contents = crate::MessageContents::new();
+        if let Some(entities) = entities {
             let mut last_pos = 0;
             for entity in entities {
-                if entity.get_offset() > last_pos {
-                    contents = contents.text(String::from_utf16(
-                        &utf16_text[last_pos..entity.get_offset()],
-                    )?);
+                let Some(base) = entity.get_base() else {
+                    continue;
+                };
+                // Nested entities are dropped, only the outermost one is kept
+                if base.offset < last_pos {
+                    continue;
+                }
+
+                let entity_text = String::from_utf16(
+                    utf16_text
+                        .get(base.offset..base.offset + base.length)
+                        .with_context(|| format!("entity `{entity:?}` is out of the text"))?,
+                )?;
+
+                if base.offset > last_pos {
+                    contents =
+                        contents.text(String::from_utf16(&utf16_text[last_pos..base.offset])?);
                 }
 
-                match &entity {
+                contents = match &entity {
                     MessageEntity::Mention { .. } => {
-                        let username = String::from_utf16(
-                            &utf16_text[entity.get_offset() + 1..
-                                entity.get_offset() + entity.get_length()],
-                        )?;
+                        let username = entity_text.strip_prefix('@').unwrap_or(&entity_text);
 
                         if username == self.self_user.get_nickname() {
-                            contents = contents.at(self.self_user.clone());
+                            contents.at(self.self_user.clone())
                         } else {
-                            contents = contents.at(crate::User::new(username));
+                            contents.at(crate::User::new(username.to_owned()))
                         }
                     },
                     MessageEntity::TextMention { user, .. } => {
-                        contents = contents.at(crate::User::new(user.id.to_string()));
+                        contents.at(crate::User::new(user.id.to_string()))
                     },
-                    MessageEntity::Other => {},
-                }
+                    _ => match entity.get_style() {
+                        Some(style) => contents.styled(entity_text, style),
+                        None => contents.text(entity_text),
+                    },
+                };
 
-                last_pos = entity.get_offset() + entity.get_length();
+                last_pos = base.offset + base.length;
             }
 
             if last_pos < utf16_text.len() {
                 contents = contents.text(String::from_utf16(&utf16_text[last_pos..])?);
             }
 
-            contents
+            Ok(contents)
         } else {
-            crate::MessageContents::new().text(text)
-        };
+            Ok(contents.text(text))
+        }
+    }
 
-        let msg = crate::Message::new(
-            message.message_id.to_string(),
-            contents,
-            if let Some(chat) = message.chat {
+    fn convert_chat(self: &Arc<Self>, chat: Option<&Chat>) -> crate::Chat<C> {
+        chat.map_or_else(
+            || crate::Chat::private(self.clone(), crate::User::new(String::new())),
+            |chat| {
                 if chat.r#type == "private" {
                     crate::Chat::private(self.clone(), crate::User::new(chat.id.to_string()))
                 } else {
                     crate::Chat::group(self.clone(), crate::Group::new(chat.id.to_string()))
                 }
-            } else {
-                crate::Chat::private(self.clone(), crate::User::new(String::new()))
             },
-            if let Some(from) = message.from {
-                crate::User::new(from.id.to_string()).nickname(format!(
-                    "{}{}",
-                    from.first_name,
-                    from.last_name
-                        .map_or_else(String::new, |last_name| format!(" {last_name}"))
-                ))
-            } else {
-                crate::User::new(String::new())
-            },
-        );
-
-        self.event_tx.send(crate::Event::Message(msg)).await?;
+        )
+    }
 
-        Ok(())
+    async fn report(self: &Arc<Self>, previous: Option<crate::api::Status>) {
+        self.health
+            .report(self.clone(), &self.event_tx, previous)
+            .await;
     }
 
     async fn call_api<R, D>(
@@ -158,7 +321,113 @@ where
             .join(api)
             .with_context(|| format!("failed to join `{}` and {api}", self.api_url))?;
 
-        call_api(url, method, req).await
+        let start = Instant::now();
+        let res = call_api(url, method, req).await;
+        metrics::api_called(&metrics::api_label(self), api, start.elapsed(), res.is_ok());
+        res
+    }
+
+    async fn send_text(&self, chat: &crate::Chat<C>, req: &SendMessageReq) -> Result<String> {
+        self.send_queue
+            .send(&send_queue::chat_key(chat), || async {
+                let resp: Message = self
+                    .call_api("sendMessage", reqwest::Method::POST, Some(req))
+                    .await?;
+
+                Ok(resp.message_id.to_string())
+            })
+            .await
+    }
+
+    /// Sends `attachment` as the `field` of `req`, uploading it if it is
+    /// local.
+    async fn send_media(
+        &self,
+        chat: &crate::Chat<C>,
+        api: &'static str,
+        field: &'static str,
+        req: &SendMediaReq,
+        attachment: crate::Attachment,
+    ) -> Result<String> {
+        let serde_json::Value::Object(mut req) =
+            serde_json::to_value(req).with_context(|| format!("failed to serialize `{req:?}`"))?
+        else {
+            anyhow::bail!("`{req:?}` is not an object");
+        };
+
+        let file_name = match (attachment.get_name(), attachment.get_source()) {
+            (Some(name), _) => name.to_owned(),
+            (None, crate::MediaSource::Path(path)) => path
+                .file_name()
+                .map_or_else(|| field.to_owned(), |name| name.to_string_lossy().into()),
+            (None, _) => field.to_owned(),
+        };
+
+        let data = match attachment.get_source() {
+            crate::MediaSource::Url(s) | crate::MediaSource::Id(s) => {
+                req.insert(field.to_owned(), s.clone().into());
+                None
+            },
+            source @ (crate::MediaSource::Path(_) | crate::MediaSource::Bytes(_)) => {
+                Some(source.read().await?)
+            },
+        };
+
+        self.send_queue
+            .send(&send_queue::chat_key(chat), || async {
+                let resp: Message = match &data {
+                    Some(data) => {
+                        let mut part = Part::bytes(data.clone()).file_name(file_name.clone());
+                        if let Some(mime_type) = attachment.get_mime_type() {
+                            part = part
+                                .mime_str(mime_type)
+                                .with_context(|| format!("invalid mime type `{mime_type}`"))?;
+                        }
+
+                        self.upload(api, &req, field, part).await?
+                    },
+                    None => {
+                        self.call_api(api, reqwest::Method::POST, Some(&req))
+                            .await?
+                    },
+                };
+
+                Ok(resp.message_id.to_string())
+            })
+            .await
+    }
+
+    async fn upload<D>(
+        &self,
+        api: &'static str,
+        req: &serde_json::Map<String, serde_json::Value>,
+        field: &'static str,
+        part: Part,
+    ) -> Result<D>
+    where
+        D: for<'de> Deserialize<'de> + Debug,
+    {
+        let url = self
+            .api_url
+            .join(api)
+            .with_context(|| format!("failed to join `{}` and {api}", self.api_url))?;
+
+        let mut form = Form::new();
+        for (name, value) in req {
+            form = form.text(
+                name.clone(),
+                match value {
+                    serde_json::Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                },
+            );
+        }
+        form = form.part(field, part);
+
+        let start = Instant::now();
+        let res = upload(url, form, format!("{req:?}")).await;
+        metrics::api_called(&metrics::api_label(self), api, start.elapsed(), res.is_ok());
+        res
     }
 }
 
@@ -175,6 +444,14 @@ where
         &self.self_user
     }
 
+    fn get_platform(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn get_max_length(&self) -> Option<usize> {
+        Some(4096)
+    }
+
     async fn run(self: Arc<Self>) {
         loop {
             let mut offset = 0;
@@ -191,6 +468,7 @@ where
                 .await;
             match resp {
                 Ok(updates) => {
+                    self.report(self.health.succeeded()).await;
                     for update in updates {
                         if update.update_id > offset {
                             offset = update.update_id;
@@ -199,6 +477,7 @@ where
                 },
                 Err(err) => {
                     tracing::error!("{err:?}");
+                    self.report(self.health.failed(&err)).await;
                     tokio::time::sleep(Duration::from_secs(3)).await;
                     continue;
                 },
@@ -217,21 +496,21 @@ where
                     .await;
                 match resp {
                     Ok(updates) => {
+                        self.report(self.health.succeeded()).await;
                         for update in updates {
                             if update.update_id > offset {
                                 offset = update.update_id;
                             }
 
-                            let self_clone = self.clone();
-                            tokio::spawn(async move {
-                                if let Err(err) = self_clone.handle_update(update).await {
-                                    tracing::error!("{err:?}");
-                                }
-                            });
+                            // Handled in order, dispatching is up to `BotMaid`
+                            if let Err(err) = self.handle_update(update).await {
+                                tracing::error!("{err:?}");
+                            }
                         }
                     },
                     Err(err) => {
                         tracing::error!("{err:?}");
+                        self.report(self.health.failed(&err)).await;
                         tokio::time::sleep(Duration::from_secs(3)).await;
                     },
                 }
@@ -239,6 +518,10 @@ where
         }
     }
 
+    fn get_health(&self) -> Health {
+        self.health.get()
+    }
+
     async fn next_event(&self) -> Option<crate::Event<C>> {
         let mut events = self.event_rx.lock().await;
         events.recv().await
@@ -248,11 +531,12 @@ where
         &self,
         contents: crate::MessageContents,
         chat: crate::Chat<C>,
-        reply_to_msg: Option<&crate::Message<C>>,
-    ) -> Result<String> {
+        reply_to_id: Option<&str>,
+    ) -> Result<Vec<String>> {
         let mut text = String::new();
         let mut entities = Vec::new();
         let mut offset = 0;
+        let mut media = Vec::new();
 
         for content in contents {
             match content {
@@ -261,14 +545,7 @@ where
                     offset += t.encode_utf16().count();
                 },
                 crate::MessageContent::At(user) => {
-                    let mention_text = format!(
-                        "@{}",
-                        if let Some(nickname) = &user.nickname {
-                            nickname.to_owned()
-                        } else {
-                            user.id.clone()
-                        }
-                    );
+                    let mention_text = format!("@{}", user.nickname.as_ref().unwrap_or(&user.id));
 
                     let mention_text_len = mention_text.encode_utf16().count();
 
@@ -287,37 +564,122 @@ where
 
                     offset += mention_text_len;
                 },
+                crate::MessageContent::Styled { text: t, style } => {
+                    let length = t.encode_utf16().count();
+
+                    entities.push(MessageEntity::from_style(
+                        style,
+                        MessageEntityBase { offset, length },
+                    ));
+                    text.push_str(&t);
+
+                    offset += length;
+                },
+                crate::MessageContent::Image(source) => {
+                    media.push(("sendPhoto", "photo", crate::Attachment::new(source)));
+                },
+                crate::MessageContent::Voice(attachment) => {
+                    media.push(("sendVoice", "voice", attachment));
+                },
+                crate::MessageContent::Video(attachment) => {
+                    media.push(("sendVideo", "video", attachment));
+                },
+                // Telegram takes MP3 and M4A files as audios, whose ids only
+                // `sendAudio` accepts
+                crate::MessageContent::File(attachment)
+                    if matches!(attachment.get_mime_type(), Some("audio/mpeg" | "audio/mp4")) =>
+                {
+                    media.push(("sendAudio", "audio", attachment));
+                },
+                crate::MessageContent::File(attachment) => {
+                    media.push(("sendDocument", "document", attachment));
+                },
+                // No merged forwards on Telegram, the nodes are quoted instead
+                crate::MessageContent::Forward(nodes) => {
+                    offset += quote_forward(&nodes, &mut text, &mut entities, offset);
+                },
             }
         }
 
-        let reply_parameters = if let Some(reply_to_msg) = reply_to_msg {
+        let mut reply_parameters = if let Some(reply_to_id) = reply_to_id {
             Some(ReplyParameters {
-                message_id: reply_to_msg.id.parse()?,
+                message_id: reply_to_id.parse()?,
             })
         } else {
             None
         };
 
-        let req = match chat.get_info() {
-            crate::ChatInfo::Private(user) => SendMessageReq {
-                chat_id: user.id.parse()?,
-                text,
-                entities,
-                reply_parameters,
-            },
-            crate::ChatInfo::Group(group) => SendMessageReq {
-                chat_id: group.id.parse()?,
+        let chat_id = match chat.get_info() {
+            crate::ChatInfo::Private(user) => user.id.parse()?,
+            crate::ChatInfo::Group(group) => group.id.parse()?,
+        };
+
+        let mut ids = Vec::new();
+        let mut message = Some((text, entities));
+
+        if !media.is_empty() {
+            // The text goes along with the first media as its caption, unless
+            // too long for one, in which case it is sent after the media
+            let mut caption =
+                message.take_if(|(text, _)| text.encode_utf16().count() <= MAX_CAPTION_LENGTH);
+            for (api, field, attachment) in media {
+                let (caption, caption_entities) = caption.take().unwrap_or_default();
+                let req = SendMediaReq {
+                    chat_id,
+                    caption,
+                    caption_entities,
+                    reply_parameters: reply_parameters.take(),
+                };
+
+                ids.push(self.send_media(&chat, api, field, &req, attachment).await?);
+            }
+        }
+
+        if let Some((text, entities)) = message {
+            let req = SendMessageReq {
+                chat_id,
                 text,
                 entities,
                 reply_parameters,
-            },
+            };
+
+            ids.push(self.send_text(&chat, &req).await?);
+        }
+
+        Ok(ids)
+    }
+
+    async fn download(&self, source: &crate::MediaSource) -> Result<Vec<u8>> {
+        let crate::MediaSource::Id(file_id) = source else {
+            return source.read().await;
         };
 
-        let resp: Message = self
-            .call_api("sendMessage", reqwest::Method::POST, Some(req))
+        let resp: File = self
+            .call_api(
+                "getFile",
+                reqwest::Method::POST,
+                Some(GetFileReq {
+                    file_id: file_id.clone(),
+                }),
+            )
             .await?;
-
-        Ok(resp.message_id.to_string())
+        let file_path = resp
+            .file_path
+            .with_context(|| format!("file `{file_id}` can no longer be downloaded"))?;
+
+        // https://api.telegram.org/file/bot<token>/<file_path>
+        let mut url = self.api_url.clone();
+        url.set_path(&format!("/file{}{file_path}", self.api_url.path()));
+
+        // The url contains the token, so it is kept out of the errors
+        Ok(reqwest::get(url)
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("failed to download file `{file_id}`"))?
+            .bytes()
+            .await
+            .with_context(|| format!("failed to download file `{file_id}`"))?
+            .to_vec())
     }
 
     async fn is_group_admin(&self, user: &crate::User, group: &crate::Group) -> Result<bool> {
@@ -348,18 +710,63 @@ where
 
     let resp: Resp<D> = food_http_rs::call_api(url, method, req)
         .await
+        .context(Error::new(ErrorKind::Network, None, None))
         .with_context(|| {
             format!("failed to call api `{url_str}({method_str})`, req: `{req_debug}`")
         })?;
 
+    into_result(resp, &url_str, &method_str, &req_debug)
+}
+
+async fn upload<D>(url: Url, form: Form, req_debug: String) -> Result<D>
+where
+    D: for<'de> Deserialize<'de> + Debug,
+{
+    let url_str = format!("{url}");
+    let method_str = format!("{}", reqwest::Method::POST);
+
+    let resp: Resp<D> = match reqwest::Client::new()
+        .post(url)
+        .multipart(form)
+        .send()
+        .await
+    {
+        Ok(resp) => resp.json().await,
+        Err(err) => Err(err),
+    }
+    .context(Error::new(ErrorKind::Network, None, None))
+    .with_context(|| format!("failed to call api `{url_str}({method_str})`, req: `{req_debug}`"))?;
+
+    into_result(resp, &url_str, &method_str, &req_debug)
+}
+
+fn into_result<D>(resp: Resp<D>, url_str: &str, method_str: &str, req_debug: &str) -> Result<D> {
     if let Some(result) = resp.result {
         Ok(result)
     } else {
         if !resp.ok {
-            anyhow::bail!(
-                "telegram api `{url_str}({method_str})` returns failed, req: `{req_debug}`, retcode: `{:?}`, error: `{:?}`",
-                resp.err_code,
-                resp.description,
+            let kind = match (resp.error_code, resp.parameters.and_then(|p| p.retry_after)) {
+                (_, Some(retry_after)) => ErrorKind::RateLimited {
+                    retry_after: Duration::from_secs(retry_after),
+                },
+                (Some(403), _) => ErrorKind::Forbidden,
+                (Some(400), _)
+                    if resp
+                        .description
+                        .as_ref()
+                        .is_some_and(|description| description.contains("chat not found")) =>
+                {
+                    ErrorKind::ChatNotFound
+                },
+                _ => ErrorKind::Other,
+            };
+
+            return Err(
+                anyhow::Error::new(Error::new(kind, resp.error_code, resp.description)).context(
+                    format!(
+                        "telegram api `{url_str}({method_str})` returns failed, req: `{req_debug}`"
+                    ),
+                ),
             );
         }
 
@@ -367,12 +774,59 @@ where
     }
 }
 
+/// Telegram has no merged forwards, the nodes are quoted instead, each on a
+/// line starting with the sender in bold.
+///
+/// Appends the quote to `text`, at `offset` in UTF-16, and returns its length.
+fn quote_forward(
+    nodes: &[crate::ForwardNode],
+    text: &mut String,
+    entities: &mut Vec<MessageEntity>,
+    offset: usize,
+) -> usize {
+    let quote_index = entities.len();
+    let mut length = 0;
+
+    for node in nodes {
+        let name = node.get_name();
+        let name_length = name.encode_utf16().count();
+
+        entities.push(MessageEntity::Bold {
+            base: MessageEntityBase {
+                offset: offset + length,
+                length: name_length,
+            },
+        });
+        text.push_str(name);
+        length += name_length;
+
+        let line = format!(": {}\n", node.get_contents());
+        text.push_str(&line);
+        length += line.encode_utf16().count();
+    }
+
+    entities.insert(
+        quote_index,
+        MessageEntity::ExpandableBlockquote {
+            base: MessageEntityBase { offset, length },
+        },
+    );
+
+    length
+}
+
 #[derive(Debug, Deserialize)]
 struct Resp<T> {
     ok: bool,
     result: Option<T>,
-    err_code: Option<i16>,
+    error_code: Option<i64>,
     description: Option<String>,
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseParameters {
+    retry_after: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -391,8 +845,12 @@ struct GetUpdatesReq {
 
 #[derive(Debug, Deserialize)]
 struct Update {
+    #[allow(clippy::struct_field_names)]
     update_id: i64,
     message: Option<Message>,
+    edited_message: Option<Message>,
+    my_chat_member: Option<ChatMemberUpdated>,
+    chat_join_request: Option<ChatJoinRequest>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -402,6 +860,18 @@ struct User {
     last_name: Option<String>,
 }
 
+impl From<&User> for crate::User {
+    fn from(user: &User) -> Self {
+        Self::new(user.id.to_string()).nickname(format!(
+            "{}{}",
+            user.first_name,
+            user.last_name
+                .as_ref()
+                .map_or_else(String::new, |last_name| format!(" {last_name}"))
+        ))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Chat {
     id: i64,
@@ -427,24 +897,100 @@ enum MessageEntity {
         #[serde(flatten)]
         base: MessageEntityBase,
     },
+    Bold {
+        #[serde(flatten)]
+        base: MessageEntityBase,
+    },
+    Italic {
+        #[serde(flatten)]
+        base: MessageEntityBase,
+    },
+    Underline {
+        #[serde(flatten)]
+        base: MessageEntityBase,
+    },
+    Strikethrough {
+        #[serde(flatten)]
+        base: MessageEntityBase,
+    },
+    Spoiler {
+        #[serde(flatten)]
+        base: MessageEntityBase,
+    },
+    Code {
+        #[serde(flatten)]
+        base: MessageEntityBase,
+    },
+    Pre {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+
+        #[serde(flatten)]
+        base: MessageEntityBase,
+    },
+    TextLink {
+        url: String,
+
+        #[serde(flatten)]
+        base: MessageEntityBase,
+    },
+    ExpandableBlockquote {
+        #[serde(flatten)]
+        base: MessageEntityBase,
+    },
     #[serde(other)]
     Other,
 }
 
 impl MessageEntity {
-    const fn get_offset(&self) -> usize {
-        match self {
-            Self::TextMention { base, .. } | Self::Mention { base, .. } => base.offset,
-            Self::Other => 0,
+    fn from_style(style: crate::Style, base: MessageEntityBase) -> Self {
+        match style {
+            crate::Style::Bold => Self::Bold { base },
+            crate::Style::Italic => Self::Italic { base },
+            crate::Style::Underline => Self::Underline { base },
+            crate::Style::Strikethrough => Self::Strikethrough { base },
+            crate::Style::Spoiler => Self::Spoiler { base },
+            crate::Style::Code => Self::Code { base },
+            crate::Style::Pre { language } => Self::Pre { language, base },
+            crate::Style::Link { url } => Self::TextLink { url, base },
         }
     }
 
-    const fn get_length(&self) -> usize {
+    const fn get_base(&self) -> Option<&MessageEntityBase> {
         match self {
-            Self::TextMention { base, .. } | Self::Mention { base, .. } => base.length,
-            Self::Other => 0,
+            Self::Mention { base } |
+            Self::TextMention { base, .. } |
+            Self::Bold { base } |
+            Self::Italic { base } |
+            Self::Underline { base } |
+            Self::Strikethrough { base } |
+            Self::Spoiler { base } |
+            Self::Code { base } |
+            Self::Pre { base, .. } |
+            Self::TextLink { base, .. } => Some(base),
+            // Quotes wrap other entities rather than style the text
+            Self::ExpandableBlockquote { .. } | Self::Other => None,
         }
     }
+
+    fn get_style(&self) -> Option<crate::Style> {
+        Some(match self {
+            Self::Bold { .. } => crate::Style::Bold,
+            Self::Italic { .. } => crate::Style::Italic,
+            Self::Underline { .. } => crate::Style::Underline,
+            Self::Strikethrough { .. } => crate::Style::Strikethrough,
+            Self::Spoiler { .. } => crate::Style::Spoiler,
+            Self::Code { .. } => crate::Style::Code,
+            Self::Pre { language, .. } => crate::Style::Pre {
+                language: language.clone(),
+            },
+            Self::TextLink { url, .. } => crate::Style::Link { url: url.clone() },
+            Self::Mention { .. } |
+            Self::TextMention { .. } |
+            Self::ExpandableBlockquote { .. } |
+            Self::Other => return None,
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -455,6 +1001,74 @@ struct Message {
     chat: Option<Chat>,
     text: Option<String>,
     entities: Option<Vec<MessageEntity>>,
+    photo: Option<Vec<PhotoSize>>,
+    voice: Option<FileInfo>,
+    audio: Option<FileInfo>,
+    video: Option<FileInfo>,
+    document: Option<FileInfo>,
+    caption: Option<String>,
+    caption_entities: Option<Vec<MessageEntity>>,
+    #[allow(clippy::struct_field_names)]
+    reply_to_message: Option<Box<Self>>,
+    new_chat_members: Option<Vec<User>>,
+    left_chat_member: Option<User>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PhotoSize {
+    file_id: String,
+}
+
+/// The part shared by voices, audios, videos and documents.
+#[derive(Debug, Serialize, Deserialize)]
+struct FileInfo {
+    file_id: String,
+    file_name: Option<String>,
+    mime_type: Option<String>,
+}
+
+impl From<FileInfo> for crate::Attachment {
+    fn from(file: FileInfo) -> Self {
+        let mut attachment = Self::new(crate::MediaSource::Id(file.file_id));
+        if let Some(file_name) = file.file_name {
+            attachment = attachment.name(file_name);
+        }
+        if let Some(mime_type) = file.mime_type {
+            attachment = attachment.mime_type(mime_type);
+        }
+
+        attachment
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GetFileReq {
+    file_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct File {
+    file_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMemberUpdated {
+    chat: Chat,
+    from: User,
+    old_chat_member: ChatMember,
+    new_chat_member: ChatMember,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMember {
+    status: ChatMemberStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatJoinRequest {
+    chat: Chat,
+    from: User,
+    bio: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -471,6 +1085,18 @@ struct SendMessageReq {
     reply_parameters: Option<ReplyParameters>,
 }
 
+/// The media itself is added by [`Telegram::send_media`].
+#[derive(Debug, Serialize)]
+struct SendMediaReq {
+    chat_id: i64,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    caption: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    caption_entities: Vec<MessageEntity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_parameters: Option<ReplyParameters>,
+}
+
 #[derive(Debug, Serialize)]
 struct GetChatMemberReq {
     chat_id: i64,
@@ -487,10 +1113,20 @@ struct GetChatMemberData {
 enum ChatMemberStatus {
     Creator,
     Administrator,
+    Member,
+    Restricted,
+    Left,
+    Kicked,
     #[serde(other)]
     Other,
 }
 
+impl ChatMemberStatus {
+    const fn is_present(&self) -> bool {
+        !matches!(self, Self::Left | Self::Kicked)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct GetMeData {
     id: i64,
@@ -0,0 +1,238 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use tokio::task::JoinHandle;
+
+use crate::middleware::{Middleware, Next};
+use crate::{
+    Attachment, Chat, Event, ForwardNode, MediaSource, Message, MessageContent, MessageContents,
+};
+
+static DEFAULT_CAPACITY: usize = 1024;
+
+/// The same message in each chat it appears in.
+pub type Mirror<C> = Vec<(Chat<C>, String)>;
+
+/// Mirrors the messages of linked chats into each other, typically across
+/// platforms.
+///
+/// Forwarded messages are prefixed with the nickname of their sender and
+/// mentions are turned into plain `@nickname` text. Messages sent by the bot
+/// itself are never forwarded. Replies to a forwarded message reply to its
+/// copies too. Messages are forwarded in order in the background, so they
+/// keep flowing down the middleware chain without waiting for the sends.
+pub struct Bridge<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    links: Vec<Vec<Chat<C>>>,
+
+    mirrors: Arc<Mutex<VecDeque<Mirror<C>>>>,
+    capacity: usize,
+
+    last_forward: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl<C> Bridge<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            links: Vec::new(),
+
+            mirrors: Arc::new(Mutex::new(VecDeque::new())),
+            capacity: DEFAULT_CAPACITY,
+
+            last_forward: Mutex::new(None),
+        }
+    }
+
+    /// Links `chats` so that a message in any of them is forwarded to all the
+    /// others. Chats are usually built with [`Chat::from_raw`].
+    pub fn link(&mut self, chats: Vec<Chat<C>>) -> &mut Self {
+        self.links.push(chats);
+        self
+    }
+
+    /// Sets how many forwarded messages are remembered for
+    /// [`Bridge::get_mirrors`].
+    pub const fn set_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Returns the copies of a message, or the original and the other copies
+    /// if it is a copy itself.
+    #[must_use]
+    pub fn get_mirrors(&self, chat: &Chat<C>, message_id: &str) -> Mirror<C> {
+        self.lock()
+            .iter()
+            .find(|mirror| {
+                mirror
+                    .iter()
+                    .any(|(c, id)| id == message_id && c.is_same(chat))
+            })
+            .map(|mirror| {
+                mirror
+                    .iter()
+                    .filter(|(c, id)| id != message_id || !c.is_same(chat))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn forward(&self, msg: &Message<C>) {
+        let targets: Vec<_> = self
+            .links
+            .iter()
+            .filter(|link| link.iter().any(|chat| chat.is_same(msg.get_chat())))
+            .flatten()
+            .filter(|chat| !chat.is_same(msg.get_chat()))
+            .cloned()
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+
+        let contents = Self::convert(msg);
+        let replied = msg
+            .get_reply_to()
+            .map(|reply_to| self.get_mirrors(msg.get_chat(), reply_to.get_id()))
+            .unwrap_or_default();
+
+        let mut mirror = vec![(msg.get_chat().clone(), msg.get_id().clone())];
+        let mirrors = self.mirrors.clone();
+        let capacity = self.capacity;
+        let mut last_forward = lock(&self.last_forward);
+        let previous = last_forward.take();
+        let forward = async move {
+            // Each forward waits for the one before, to keep the order
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+
+            for chat in targets {
+                let res = match replied.iter().find(|(c, _)| c.is_same(&chat)) {
+                    Some((_, id)) => chat.reply_to_id(id, contents.clone()).await,
+                    None => chat.send_msg_parts(contents.clone()).await,
+                };
+                // Long messages may be split into several parts
+                match res {
+                    Ok(ids) => mirror.extend(ids.into_iter().map(|id| (chat.clone(), id))),
+                    Err(err) => tracing::error!("failed to forward message to [{chat:?}]: {err:?}"),
+                }
+            }
+
+            let mut mirrors = lock(&mirrors);
+            mirrors.push_back(mirror);
+            while mirrors.len() > capacity {
+                mirrors.pop_front();
+            }
+            drop(mirrors);
+        };
+
+        // Waited for on shutdown along with the handlers
+        *last_forward = Some(match &msg.get_chat().runtime {
+            Some(runtime) => runtime.tracker.spawn(forward),
+            None => tokio::spawn(forward),
+        });
+    }
+
+    fn convert(msg: &Message<C>) -> MessageContents {
+        let sender = msg.get_sender();
+        let nickname = match sender.get_nickname() {
+            "" => sender.get_id(),
+            nickname => nickname,
+        };
+
+        Self::convert_contents(
+            MessageContents::new().text(format!("[{nickname}] ")),
+            msg.get_contents(),
+        )
+    }
+
+    fn convert_contents(mut contents: MessageContents, from: &MessageContents) -> MessageContents {
+        for content in from {
+            contents = match content {
+                MessageContent::Text(text) => contents.text(text),
+                MessageContent::Styled { text, style } => contents.styled(text, style.clone()),
+                // Ids of files are not understood by the other platforms
+                MessageContent::Image(MediaSource::Id(_)) => contents.text("[image] "),
+                MessageContent::Image(source) => contents.image(source.clone()),
+                MessageContent::Voice(attachment) if is_id(attachment) => {
+                    contents.text(format!("[voice: {attachment}] "))
+                },
+                MessageContent::Voice(attachment) => contents.voice(attachment.clone()),
+                MessageContent::Video(attachment) if is_id(attachment) => {
+                    contents.text(format!("[video: {attachment}] "))
+                },
+                MessageContent::Video(attachment) => contents.video(attachment.clone()),
+                MessageContent::File(attachment) if is_id(attachment) => {
+                    contents.text(format!("[file: {attachment}] "))
+                },
+                MessageContent::File(attachment) => contents.file(attachment.clone()),
+                MessageContent::Forward(nodes) => contents.forward(
+                    nodes
+                        .iter()
+                        .map(|node| {
+                            ForwardNode::new(
+                                node.get_sender().clone(),
+                                Self::convert_contents(MessageContents::new(), node.get_contents()),
+                            )
+                        })
+                        .collect(),
+                ),
+                MessageContent::At(user) => match user.get_nickname() {
+                    "" => contents.text(format!("@{} ", user.get_id())),
+                    nickname => contents.text(format!("@{nickname} ")),
+                },
+            };
+        }
+
+        contents
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Mirror<C>>> {
+        lock(&self.mirrors)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+const fn is_id(attachment: &Attachment) -> bool {
+    matches!(attachment.get_source(), MediaSource::Id(_))
+}
+
+impl<C> Default for Bridge<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl<C> Middleware<C> for Bridge<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    async fn handle(&self, event: Event<C>, next: Next<'_, C>) -> Result<()> {
+        if let Event::Message(msg) = &event &&
+            msg.get_sender().get_id() != msg.get_api().get_self_user().get_id()
+        {
+            self.forward(msg);
+        }
+
+        next.run(event).await
+    }
+}
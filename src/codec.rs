@@ -0,0 +1,492 @@
+use std::path::PathBuf;
+use std::str::Chars;
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::{Attachment, ForwardNode, MediaSource, MessageContent, MessageContents, Style, User};
+
+/// Parses `code` into contents.
+///
+/// Text is written as is, while other contents are tags in brackets:
+///
+/// - `[at:12345]` or `[at:12345,nickname=Alice]`
+/// - `[bold:text]`, `[italic:text]`, `[underline:text]`,
+///   `[strikethrough:text]`, `[spoiler:text]`, `[code:text]`,
+///   `[pre:text,language=rust]` and `[link:text,url=https://example.com]`
+/// - `[image,url=...]`, with `path=...`, `id=...` or `base64=...` instead of
+///   `url` for other sources
+/// - `[voice,...]`, `[video,...]` and `[file,...]`, with the same sources as
+///   images and optionally `name=...` and `mime_type=...`
+/// - `[forward][node:12345,nickname=Alice]text[node:67890]text[/forward]`
+///
+/// A backslash escapes the next char, which is needed for `\`, `[` and `]` in
+/// text, and for `\`, `,` and `]` in tags.
+///
+/// # Errors
+///
+/// Fails on unknown tags, missing parameters and unclosed tags or forwards.
+pub fn parse(code: &str) -> Result<MessageContents> {
+    let mut parser = Parser {
+        chars: code.chars(),
+    };
+
+    match parser.parse_contents()? {
+        (contents, None) => Ok(contents),
+        (_, Some(_)) => anyhow::bail!("`[node]` or `[/forward]` outside of a forward in `{code}`"),
+    }
+}
+
+/// Renders `contents` as code, the reverse of [`parse`].
+#[must_use]
+pub fn render(contents: &MessageContents) -> String {
+    let mut code = String::new();
+
+    for content in contents {
+        match content {
+            MessageContent::Text(text) => escape_into(&mut code, text, &['\\', '[', ']']),
+            MessageContent::At(user) => code.push_str(&tag(
+                "at",
+                Some(user.get_id()),
+                &[("nickname", user.nickname.as_deref())],
+            )),
+            MessageContent::Styled { text, style } => code.push_str(&match style {
+                Style::Bold => tag("bold", Some(text), &[]),
+                Style::Italic => tag("italic", Some(text), &[]),
+                Style::Underline => tag("underline", Some(text), &[]),
+                Style::Strikethrough => tag("strikethrough", Some(text), &[]),
+                Style::Spoiler => tag("spoiler", Some(text), &[]),
+                Style::Code => tag("code", Some(text), &[]),
+                Style::Pre { language } => {
+                    tag("pre", Some(text), &[("language", language.as_deref())])
+                },
+                Style::Link { url } => tag("link", Some(text), &[("url", Some(url))]),
+            }),
+            MessageContent::Image(source) => {
+                let (key, value) = source_param(source);
+                code.push_str(&tag("image", None, &[(key, Some(&value))]));
+            },
+            MessageContent::Voice(attachment) => {
+                code.push_str(&attachment_tag("voice", attachment));
+            },
+            MessageContent::Video(attachment) => {
+                code.push_str(&attachment_tag("video", attachment));
+            },
+            MessageContent::File(attachment) => code.push_str(&attachment_tag("file", attachment)),
+            MessageContent::Forward(nodes) => {
+                code.push_str("[forward]");
+                for node in nodes {
+                    let sender = node.get_sender();
+                    code.push_str(&tag(
+                        "node",
+                        Some(sender.get_id()),
+                        &[("nickname", sender.nickname.as_deref())],
+                    ));
+                    code.push_str(&render(node.get_contents()));
+                }
+                code.push_str("[/forward]");
+            },
+        }
+    }
+
+    code
+}
+
+/// Serializes contents as code, for `#[serde(with = "botmaid_rs::codec")]`.
+///
+/// # Errors
+pub fn serialize<S>(contents: &MessageContents, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&render(contents))
+}
+
+/// Deserializes contents from code, for `#[serde(with = "botmaid_rs::codec")]`.
+///
+/// # Errors
+pub fn deserialize<'de, D>(deserializer: D) -> Result<MessageContents, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let code = String::deserialize(deserializer)?;
+    parse(&code).map_err(|err| serde::de::Error::custom(format!("{err:#}")))
+}
+
+/// Serializes [`MediaSource::Bytes`] as base64 rather than an array of
+/// numbers.
+pub(crate) mod bytes {
+    use base64::Engine;
+    use base64::prelude::BASE64_STANDARD;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&BASE64_STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = String::deserialize(deserializer)?;
+        BASE64_STANDARD
+            .decode(data)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+struct Tag {
+    kind: String,
+    value: Option<String>,
+    params: Vec<(String, String)>,
+}
+
+impl Tag {
+    fn get_value(&self) -> Result<String> {
+        self.value
+            .clone()
+            .with_context(|| format!("no value in `[{}]`", self.kind))
+    }
+
+    fn get_param(&self, key: &str) -> Option<String> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn get_source(&self) -> Result<MediaSource> {
+        Ok(if let Some(url) = self.get_param("url") {
+            MediaSource::Url(url)
+        } else if let Some(path) = self.get_param("path") {
+            MediaSource::Path(PathBuf::from(path))
+        } else if let Some(id) = self.get_param("id") {
+            MediaSource::Id(id)
+        } else if let Some(data) = self.get_param("base64") {
+            MediaSource::Bytes(
+                BASE64_STANDARD
+                    .decode(data)
+                    .with_context(|| format!("invalid base64 in `[{}]`", self.kind))?,
+            )
+        } else {
+            anyhow::bail!("no url, path, id or base64 in `[{}]`", self.kind);
+        })
+    }
+
+    fn get_attachment(&self) -> Result<Attachment> {
+        let mut attachment = Attachment::new(self.get_source()?);
+        if let Some(name) = self.get_param("name") {
+            attachment = attachment.name(name);
+        }
+        if let Some(mime_type) = self.get_param("mime_type") {
+            attachment = attachment.mime_type(mime_type);
+        }
+
+        Ok(attachment)
+    }
+
+    fn get_user(&self) -> Result<User> {
+        let user = User::new(self.get_value()?);
+
+        Ok(match self.get_param("nickname") {
+            Some(nickname) => user.nickname(nickname),
+            None => user,
+        })
+    }
+}
+
+/// Where [`Parser::parse_contents`] stops short of the end of the code.
+enum Stop {
+    Node(User),
+    EndForward,
+}
+
+struct Parser<'a> {
+    chars: Chars<'a>,
+}
+
+impl Parser<'_> {
+    /// Parses contents until the end of the code or of a forward node.
+    fn parse_contents(&mut self) -> Result<(MessageContents, Option<Stop>)> {
+        let mut contents = MessageContents::new();
+        let mut text = String::new();
+
+        while let Some(c) = self.chars.next() {
+            match c {
+                '\\' => text.push(self.chars.next().unwrap_or('\\')),
+                '[' => {
+                    if !text.is_empty() {
+                        contents = contents.text(std::mem::take(&mut text));
+                    }
+
+                    let tag = self.parse_tag()?;
+                    contents = match tag.kind.as_str() {
+                        "at" => contents.at(tag.get_user()?),
+                        "bold" => contents.bold(tag.get_value()?),
+                        "italic" => contents.italic(tag.get_value()?),
+                        "underline" => contents.underline(tag.get_value()?),
+                        "strikethrough" => contents.strikethrough(tag.get_value()?),
+                        "spoiler" => contents.spoiler(tag.get_value()?),
+                        "code" => contents.code(tag.get_value()?),
+                        "pre" => contents.pre(tag.get_value()?, tag.get_param("language")),
+                        "link" => contents.link(
+                            tag.get_value()?,
+                            tag.get_param("url").context("no url in `[link]`")?,
+                        ),
+                        "image" => contents.image(tag.get_source()?),
+                        "voice" => contents.voice(tag.get_attachment()?),
+                        "video" => contents.video(tag.get_attachment()?),
+                        "file" => contents.file(tag.get_attachment()?),
+                        "forward" => contents.forward(self.parse_forward()?),
+                        "node" => return Ok((contents, Some(Stop::Node(tag.get_user()?)))),
+                        "/forward" => return Ok((contents, Some(Stop::EndForward))),
+                        kind => anyhow::bail!("unknown tag `[{kind}]`"),
+                    };
+                },
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            contents = contents.text(text);
+        }
+
+        Ok((contents, None))
+    }
+
+    fn parse_forward(&mut self) -> Result<Vec<ForwardNode>> {
+        let (leading, mut stop) = self.parse_contents()?;
+        // Only whitespace is allowed before the first node, for readability
+        if !leading
+            .iter()
+            .all(|content| matches!(content, MessageContent::Text(text) if text.trim().is_empty()))
+        {
+            anyhow::bail!("contents outside of nodes in `[forward]`");
+        }
+
+        let mut nodes = Vec::new();
+        loop {
+            match stop {
+                Some(Stop::Node(sender)) => {
+                    let (contents, next) = self.parse_contents()?;
+                    nodes.push(ForwardNode::new(sender, contents));
+                    stop = next;
+                },
+                Some(Stop::EndForward) => return Ok(nodes),
+                None => anyhow::bail!("unterminated `[forward]`"),
+            }
+        }
+    }
+
+    /// Parses a tag after its `[`.
+    fn parse_tag(&mut self) -> Result<Tag> {
+        // Each field with the position of its separator, `:` in the first and
+        // `=` in the others
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut separator = None;
+
+        loop {
+            let c = self.chars.next().context("unterminated tag")?;
+            match c {
+                '\\' => field.push(self.chars.next().context("unterminated tag")?),
+                ']' => {
+                    fields.push((field, separator));
+                    break;
+                },
+                ',' => fields.push((std::mem::take(&mut field), separator.take())),
+                ':' if fields.is_empty() && separator.is_none() => {
+                    separator = Some(field.len());
+                    field.push(c);
+                },
+                '=' if !fields.is_empty() && separator.is_none() => {
+                    separator = Some(field.len());
+                    field.push(c);
+                },
+                c => field.push(c),
+            }
+        }
+
+        let mut fields = fields.into_iter();
+        let (kind, value) = match fields.next() {
+            Some((field, Some(i))) => (field[..i].to_owned(), Some(field[i + 1..].to_owned())),
+            Some((field, None)) => (field, None),
+            None => (String::new(), None),
+        };
+
+        let params = fields
+            .map(|(field, separator)| {
+                let i = separator
+                    .with_context(|| format!("no `=` in parameter `{field}` of `[{kind}]`"))?;
+                Ok((field[..i].to_owned(), field[i + 1..].to_owned()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Tag {
+            kind,
+            value,
+            params,
+        })
+    }
+}
+
+fn tag(kind: &str, value: Option<&str>, params: &[(&str, Option<&str>)]) -> String {
+    let mut tag = format!("[{kind}");
+
+    if let Some(value) = value {
+        tag.push(':');
+        escape_into(&mut tag, value, &['\\', ',', ']']);
+    }
+    for (key, value) in params {
+        if let Some(value) = value {
+            tag.push(',');
+            tag.push_str(key);
+            tag.push('=');
+            escape_into(&mut tag, value, &['\\', ',', ']']);
+        }
+    }
+
+    tag.push(']');
+    tag
+}
+
+fn attachment_tag(kind: &str, attachment: &Attachment) -> String {
+    let (key, value) = source_param(attachment.get_source());
+
+    tag(
+        kind,
+        None,
+        &[
+            (key, Some(&value)),
+            ("name", attachment.get_name()),
+            ("mime_type", attachment.get_mime_type()),
+        ],
+    )
+}
+
+fn source_param(source: &MediaSource) -> (&'static str, String) {
+    match source {
+        MediaSource::Url(url) => ("url", url.clone()),
+        MediaSource::Path(path) => ("path", path.to_string_lossy().into_owned()),
+        MediaSource::Id(id) => ("id", id.clone()),
+        MediaSource::Bytes(bytes) => ("base64", BASE64_STANDARD.encode(bytes)),
+    }
+}
+
+fn escape_into(out: &mut String, s: &str, special: &[char]) {
+    for c in s.chars() {
+        if special.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Contents are compared by their debug output, as they have no `PartialEq`.
+    fn assert_round_trip(contents: &MessageContents) {
+        let code = render(contents);
+        let parsed = parse(&code).unwrap_or_else(|err| panic!("failed to parse `{code}`: {err:#}"));
+        assert_eq!(format!("{parsed:?}"), format!("{contents:?}"), "{code}");
+    }
+
+    fn user(id: &str, nickname: &str) -> User {
+        User::new(id.to_owned()).nickname(nickname.to_owned())
+    }
+
+    #[test]
+    fn round_trip_escapes() {
+        assert_round_trip(
+            &MessageContents::new()
+                .text(r"a\b [c] d,e:f=g \")
+                .at(user("1,2]", r"x:y=z\[w],"))
+                .at(User::new("3".to_owned()))
+                .bold(r"b\o,l:d=]")
+                .code("[code]")
+                .pre("fn main() {}", Some("ru,st".to_owned()))
+                .pre("no language", None)
+                .link("t:e=x,t", "https://example.com/?a=1,2&b=[3]".to_owned())
+                .image(MediaSource::Url(
+                    "https://example.com/a.png?x=1,y=2".to_owned(),
+                ))
+                .voice(
+                    Attachment::new(MediaSource::Id("v:1=2".to_owned()))
+                        .name("a,b].ogg".to_owned())
+                        .mime_type("audio/ogg".to_owned()),
+                )
+                .video(Attachment::new(MediaSource::Path(PathBuf::from(
+                    r"C:\videos\a=b.mp4",
+                ))))
+                .file(Attachment::new(MediaSource::Bytes(vec![0, 1, 254, 255]))),
+        );
+    }
+
+    #[test]
+    fn round_trip_forwards() {
+        let inner = vec![
+            ForwardNode::new(user("3", "C"), MessageContents::new().text("deep [1]")),
+            ForwardNode::new(User::new("4".to_owned()), MessageContents::new()),
+        ];
+        let nodes = vec![
+            ForwardNode::new(
+                user("1", "A,]"),
+                MessageContents::new().text("hi\n").bold("there"),
+            ),
+            ForwardNode::new(User::new("2".to_owned()), MessageContents::new()),
+            ForwardNode::new(
+                user("5", "E"),
+                MessageContents::new()
+                    .text("look: ")
+                    .forward(inner)
+                    .text(" end"),
+            ),
+        ];
+
+        assert_round_trip(&MessageContents::new().text("before ").forward(nodes));
+        assert_round_trip(&MessageContents::new().forward(Vec::new()));
+    }
+
+    #[test]
+    fn render_escapes() {
+        assert_eq!(
+            render(&MessageContents::new().text(r"[a]\").bold("b,c]:d=e")),
+            r"\[a\]\\[bold:b\,c\]:d=e]"
+        );
+    }
+
+    #[test]
+    fn parse_allows_whitespace_before_nodes() {
+        let contents = parse("[forward]\n  [node:1]a[node:2,nickname=B]b[/forward]").unwrap();
+        assert_eq!(
+            render(&contents),
+            "[forward][node:1]a[node:2,nickname=B]b[/forward]"
+        );
+    }
+
+    #[test]
+    fn parse_fails() {
+        for code in [
+            "[unknown]",
+            "[bold]",
+            "[bold:unclosed",
+            "[link:text]",
+            "[image]",
+            "[image,base64=!]",
+            "[at:1,nickname]",
+            "[forward][node:1]a",
+            "[forward]a[node:1]b[/forward]",
+            "[node:1]a",
+            "[/forward]",
+        ] {
+            assert!(parse(code).is_err(), "{code}");
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::text::{Mention, tokenize};
+use crate::{Message, MessageContents};
+
+#[derive(Clone, Debug)]
+pub enum Prefix {
+    /// A leading text such as `/` or `!`.
+    Text(String),
+    /// A leading mention of the bot, i.e. [`Message::be_at`].
+    At,
+}
+
+#[async_trait::async_trait]
+pub trait CommandHandler<C>: Send + Sync + 'static
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    async fn handle(&self, invocation: Invocation<C>) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<C, F, Fut> CommandHandler<C> for F
+where
+    C: Clone + Debug + Send + Sync + 'static,
+    F: Fn(Invocation<C>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    async fn handle(&self, invocation: Invocation<C>) -> Result<()> {
+        self(invocation).await
+    }
+}
+
+pub struct Command<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    name: String,
+    aliases: Vec<String>,
+    permission: Option<String>,
+    handler: Arc<dyn CommandHandler<C>>,
+}
+
+impl<C> Command<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    pub fn alias(&mut self, alias: &str) -> &mut Self {
+        self.aliases.push(alias.to_owned());
+        self
+    }
+
+    /// Only lets senders holding `permission` run the command, see
+    /// [`Permissions`](crate::permission::Permissions).
+    pub fn permission(&mut self, permission: &str) -> &mut Self {
+        self.permission = Some(permission.to_owned());
+        self
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn get_aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    #[must_use]
+    pub fn get_permission(&self) -> Option<&str> {
+        self.permission.as_deref()
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+}
+
+pub struct Router<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    prefixes: Vec<Prefix>,
+    commands: Vec<Command<C>>,
+    fallback: Option<Arc<dyn CommandHandler<C>>>,
+    denied: Option<Arc<dyn CommandHandler<C>>>,
+}
+
+impl<C> Router<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            prefixes: Vec::new(),
+            commands: Vec::new(),
+            fallback: None,
+            denied: None,
+        }
+    }
+
+    pub fn add_prefix(&mut self, prefix: Prefix) -> &mut Self {
+        self.prefixes.push(prefix);
+        self
+    }
+
+    pub fn add_command<H>(&mut self, name: &str, handler: H) -> &mut Command<C>
+    where
+        H: CommandHandler<C>,
+    {
+        self.commands.push(Command {
+            name: name.to_owned(),
+            aliases: Vec::new(),
+            permission: None,
+            handler: Arc::new(handler),
+        });
+
+        let last = self.commands.len() - 1;
+        &mut self.commands[last]
+    }
+
+    /// Sets the handler called for prefixed messages naming an unknown command.
+    pub fn set_fallback<H>(&mut self, handler: H) -> &mut Self
+    where
+        H: CommandHandler<C>,
+    {
+        self.fallback = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets the handler called instead of a command the sender lacks the
+    /// permission for. Such invocations are dropped silently by default.
+    pub fn set_denied<H>(&mut self, handler: H) -> &mut Self
+    where
+        H: CommandHandler<C>,
+    {
+        self.denied = Some(Arc::new(handler));
+        self
+    }
+
+    #[must_use]
+    pub fn get_commands(&self) -> &[Command<C>] {
+        &self.commands
+    }
+
+    /// Returns `None` if `msg` is not addressed to the router at all.
+    ///
+    /// The arguments are split as by [`MessageContents::args`], a quote left
+    /// open being taken literally.
+    #[must_use]
+    pub fn parse(&self, msg: &Message<C>) -> Option<Invocation<C>> {
+        self.resolve(msg).map(|(invocation, ..)| invocation)
+    }
+
+    /// Returns whether `msg` was taken as a command, including by the fallback
+    /// and including commands denied for lack of permission.
+    ///
+    /// # Errors
+    pub async fn dispatch(&self, msg: Message<C>) -> Result<bool> {
+        let Some((invocation, handler, permission)) = self.resolve(&msg) else {
+            return Ok(false);
+        };
+
+        if let Some(permission) = permission &&
+            !msg.has_permission(permission).await?
+        {
+            tracing::info!(
+                "denying command `{}` without permission `{permission}`",
+                invocation.name
+            );
+
+            if let Some(denied) = &self.denied {
+                denied.handle(invocation).await?;
+            }
+
+            return Ok(true);
+        }
+
+        tracing::info!(
+            "dispatching command `{}` with args {:?}",
+            invocation.name,
+            invocation.args
+        );
+
+        handler.handle(invocation).await?;
+
+        Ok(true)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn resolve(
+        &self,
+        msg: &Message<C>,
+    ) -> Option<(Invocation<C>, Arc<dyn CommandHandler<C>>, Option<&str>)> {
+        let self_user = msg.get_api().get_self_user();
+
+        let stripped = msg.get_contents().strip_mention(self_user);
+        let mentioned = stripped.is_some();
+
+        let line = stripped
+            .as_ref()
+            .unwrap_or_else(|| msg.get_contents())
+            .plain_text(Mention::Id);
+        let line = line.trim_start();
+
+        let rest = self.prefixes.iter().find_map(|prefix| match prefix {
+            Prefix::Text(text) => line.strip_prefix(text.as_str()),
+            Prefix::At => None,
+        });
+        let rest = match rest {
+            Some(rest) => rest,
+            None if mentioned && self.prefixes.iter().any(|p| matches!(p, Prefix::At)) => line,
+            None => return None,
+        };
+
+        let mut args = tokenize(rest).into_iter();
+        let mut name = args.next()?;
+
+        // Telegram style `/cmd@bot_username`
+        if let Some(at) = name.find('@') {
+            // Usernames are case-insensitive
+            if !name[at + 1..].eq_ignore_ascii_case(self_user.get_nickname()) {
+                return None;
+            }
+            name.truncate(at);
+        }
+
+        let (handler, permission) =
+            match self.commands.iter().find(|command| command.matches(&name)) {
+                Some(command) => {
+                    name.clone_from(&command.name);
+                    (command.handler.clone(), command.get_permission())
+                },
+                None => match &self.fallback {
+                    Some(fallback) => (fallback.clone(), None),
+                    None => return None,
+                },
+            };
+
+        Some((
+            Invocation {
+                name,
+                args: args.collect(),
+                msg: msg.clone(),
+            },
+            handler,
+            permission,
+        ))
+    }
+}
+
+impl<C> Default for Router<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Invocation<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    name: String,
+    args: Vec<String>,
+    msg: Message<C>,
+}
+
+impl<C> Invocation<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    /// The canonical command name, or the name as typed for the fallback.
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn get_args(&self) -> &[String] {
+        &self.args
+    }
+
+    #[must_use]
+    pub const fn get_msg(&self) -> &Message<C> {
+        &self.msg
+    }
+
+    /// # Errors
+    pub async fn reply(&self, contents: MessageContents) -> Result<String> {
+        self.msg.reply(contents).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::cli::Cli;
+    use crate::api::mock::Mock;
+    use crate::{BotAPI, Event, User};
+
+    fn router() -> Router<()> {
+        let mut router = Router::new();
+        router
+            .add_prefix(Prefix::Text("/".to_owned()))
+            .add_prefix(Prefix::At);
+        router.add_command("help", |_| async { Ok(()) });
+        router
+    }
+
+    fn assert_invocation(router: &Router<()>, msg: &Message<()>, args: &[&str]) {
+        let invocation = router
+            .parse(msg)
+            .unwrap_or_else(|| panic!("{:?} is not a command", msg.get_contents()));
+        assert_eq!(invocation.get_name(), "help");
+        assert_eq!(invocation.get_args(), args);
+    }
+
+    async fn next_msg<A: BotAPI<()>>(api: &A) -> Message<()> {
+        match api.next_event().await {
+            Some(Event::Message(msg)) => msg,
+            _ => panic!("no message"),
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_cli_lines() {
+        let router = router();
+        let cli = Arc::new(Cli::new(()));
+
+        for (line, args) in [
+            ("/help", &[][..]),
+            ("/help a 'b c'", &["a", "b c"]),
+            ("@- help a", &["a"]),
+            ("@- /help a", &["a"]),
+        ] {
+            cli.handle_line(Some(line.to_owned())).await;
+            assert_invocation(&router, &next_msg(&*cli).await, args);
+        }
+
+        cli.handle_line(Some("help".to_owned())).await;
+        assert!(router.parse(&next_msg(&*cli).await).is_none());
+    }
+
+    #[tokio::test]
+    async fn parse_mock_messages() {
+        let router = router();
+        let mock = Arc::new(
+            Mock::new(()).self_user(User::new("1".to_owned()).nickname("Maid_Bot".to_owned())),
+        );
+        let bot = mock.get_self_user().clone();
+
+        mock.simple_text("/help@maid_bot a");
+        assert_invocation(&router, &next_msg(&*mock).await, &["a"]);
+
+        mock.simple_msg(MessageContents::new().at(bot).text(" help a"));
+        assert_invocation(&router, &next_msg(&*mock).await, &["a"]);
+
+        mock.simple_text("/help@other_bot a");
+        assert!(router.parse(&next_msg(&*mock).await).is_none());
+    }
+}
@@ -0,0 +1,412 @@
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::api::BotAPI;
+use crate::api::cli::Cli;
+use crate::api::onebot_11::OneBot11;
+use crate::api::send_queue::RateLimit;
+use crate::api::telegram::Telegram;
+use crate::{BotInstance, BotMaidBuilder};
+
+/// The APIs of a bot, applied with
+/// [`BotMaidBuilder::apply_config`](crate::BotMaidBuilder::apply_config).
+///
+/// `${VAR}` and `${VAR:-default}` anywhere in the file but in comments are
+/// replaced with the environment variable `VAR` before parsing, so secrets can
+/// stay out of it.
+/// The replacement is textual, so quote it where the value must be a string.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    apis: Vec<ApiConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ApiConfig {
+    Telegram {
+        token: String,
+        rate_limit: Option<RateLimitConfig>,
+        #[serde(default)]
+        owners: Vec<String>,
+    },
+    #[serde(rename = "onebot_11")]
+    OneBot11 {
+        host: String,
+        ws_port: u16,
+        http_port: u16,
+        rate_limit: Option<RateLimitConfig>,
+        #[serde(default)]
+        owners: Vec<String>,
+        /// See [`OneBot11::read_local_files`].
+        #[serde(default)]
+        read_local_files: bool,
+    },
+    Cli {},
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    per_chat: Option<RateConfig>,
+    global: Option<RateConfig>,
+    max_retries: Option<u32>,
+}
+
+/// At most `count` messages every `period` seconds.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateConfig {
+    count: u32,
+    period: u64,
+}
+
+impl Config {
+    /// Reads a `.toml`, `.yaml` or `.yml` file.
+    ///
+    /// # Errors
+    pub fn from_file<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        let s = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config {}", path.display()))?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&s),
+            Some("yaml" | "yml") => Self::from_yaml(&s),
+            _ => anyhow::bail!(
+                "config {} is neither `.toml` nor `.yaml`/`.yml`",
+                path.display()
+            ),
+        }
+        .with_context(|| format!("invalid config {}", path.display()))
+    }
+
+    /// # Errors
+    pub fn from_toml(s: &str) -> Result<Self> {
+        let config: Self =
+            toml::from_str(&substitute_env(s)?).context("failed to parse toml config")?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// # Errors
+    pub fn from_yaml(s: &str) -> Result<Self> {
+        let config: Self =
+            serde_yaml::from_str(&substitute_env(s)?).context("failed to parse yaml config")?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    #[must_use]
+    pub fn get_apis(&self) -> &[ApiConfig] {
+        &self.apis
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.apis.is_empty() {
+            anyhow::bail!("no api is configured");
+        }
+
+        for (i, api) in self.apis.iter().enumerate() {
+            api.validate()
+                .with_context(|| format!("invalid `apis[{i}]`"))?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn apply<I, C>(
+        &self,
+        builder: &mut BotMaidBuilder<I, C>,
+        context: &C,
+    ) -> Result<()>
+    where
+        I: BotInstance<C>,
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        let mut apis: Vec<Arc<dyn BotAPI<C>>> = Vec::with_capacity(self.apis.len());
+        let mut api_owners = Vec::with_capacity(self.apis.len());
+        for (i, api) in self.apis.iter().enumerate() {
+            api_owners.push(match api {
+                ApiConfig::Telegram {
+                    token,
+                    rate_limit,
+                    owners,
+                } => {
+                    let mut telegram = Telegram::new(token, context.clone())
+                        .await
+                        .with_context(|| format!("failed to create `apis[{i}]`"))?;
+                    if let Some(rate_limit) = rate_limit {
+                        telegram = telegram.rate_limit(rate_limit.to_rate_limit());
+                    }
+
+                    apis.push(Arc::new(telegram));
+                    owners.as_slice()
+                },
+                ApiConfig::OneBot11 {
+                    host,
+                    ws_port,
+                    http_port,
+                    rate_limit,
+                    owners,
+                    read_local_files,
+                } => {
+                    let mut onebot_11 = OneBot11::new(host, *ws_port, *http_port, context.clone())
+                        .await
+                        .with_context(|| format!("failed to create `apis[{i}]`"))?
+                        .read_local_files(*read_local_files);
+                    if let Some(rate_limit) = rate_limit {
+                        onebot_11 = onebot_11.rate_limit(rate_limit.to_rate_limit());
+                    }
+
+                    apis.push(Arc::new(onebot_11));
+                    owners.as_slice()
+                },
+                ApiConfig::Cli {} => {
+                    apis.push(Arc::new(Cli::new(context.clone())));
+                    [].as_slice()
+                },
+            });
+        }
+
+        // Added only once all are created, so that a failure adds none
+        let permissions = builder.permissions();
+        for (api, owners) in apis.into_iter().zip(api_owners) {
+            for owner in owners {
+                permissions.add_owner(builder.apis.len(), owner);
+            }
+            builder.apis.push(api);
+        }
+
+        Ok(())
+    }
+}
+
+impl ApiConfig {
+    fn validate(&self) -> Result<()> {
+        match self {
+            Self::Telegram {
+                token, rate_limit, ..
+            } => {
+                if token.trim().is_empty() {
+                    anyhow::bail!("`token` of telegram is empty");
+                }
+
+                rate_limit
+                    .as_ref()
+                    .map_or(Ok(()), RateLimitConfig::validate)
+            },
+            Self::OneBot11 {
+                host,
+                ws_port,
+                http_port,
+                rate_limit,
+                ..
+            } => {
+                if host.trim().is_empty() {
+                    anyhow::bail!("`host` of onebot_11 is empty");
+                }
+                if *ws_port == 0 || *http_port == 0 {
+                    anyhow::bail!("`ws_port` and `http_port` of onebot_11 must not be 0");
+                }
+
+                rate_limit
+                    .as_ref()
+                    .map_or(Ok(()), RateLimitConfig::validate)
+            },
+            Self::Cli {} => Ok(()),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    fn validate(&self) -> Result<()> {
+        for (name, rate) in [("per_chat", self.per_chat), ("global", self.global)] {
+            if let Some(rate) = rate &&
+                (rate.count == 0 || rate.period == 0)
+            {
+                anyhow::bail!("`count` and `period` of `rate_limit.{name}` must not be 0");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_rate_limit(self) -> RateLimit {
+        let mut rate_limit = RateLimit::unlimited();
+
+        if let Some(RateConfig { count, period }) = self.per_chat {
+            rate_limit = rate_limit.per_chat(count, Duration::from_secs(period));
+        }
+        if let Some(RateConfig { count, period }) = self.global {
+            rate_limit = rate_limit.global(count, Duration::from_secs(period));
+        }
+        if let Some(max_retries) = self.max_retries {
+            rate_limit = rate_limit.max_retries(max_retries);
+        }
+
+        rate_limit
+    }
+}
+
+/// Replaces `${VAR}` and `${VAR:-default}` with the environment variable
+/// `VAR`, and `$${` with a literal `${`, except in comments.
+fn substitute_env(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+
+    for line in s.split_inclusive('\n') {
+        let (code, comment) = line.split_at(find_comment(line).unwrap_or(line.len()));
+        substitute_env_into(code, &mut out)?;
+        out.push_str(comment);
+    }
+
+    Ok(out)
+}
+
+fn substitute_env_into(s: &str, out: &mut String) -> Result<()> {
+    let mut rest = s;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            out.push_str("${");
+            rest = escaped;
+            continue;
+        }
+
+        let Some(expr) = rest.strip_prefix("${") else {
+            out.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+
+        let end = expr.find('}').with_context(|| {
+            format!(
+                "unterminated `${{` in `{}`",
+                rest.lines().next().unwrap_or_default()
+            )
+        })?;
+        let (name, default) = match expr[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&expr[..end], None),
+        };
+
+        match (std::env::var(name), default) {
+            (Ok(value), _) => out.push_str(&value),
+            (Err(_), Some(default)) => out.push_str(default),
+            (Err(err), None) => {
+                return Err(err).with_context(|| {
+                    format!("failed to substitute environment variable `{name}`")
+                });
+            },
+        }
+
+        rest = &expr[end + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(())
+}
+
+/// Finds where the `#` comment of a TOML or YAML line starts. Quotes only
+/// open strings at the start of a value, so that apostrophes in plain YAML
+/// values such as `don't` are not taken as quotes.
+fn find_comment(line: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut closed = false;
+    let mut previous = None;
+
+    for (i, c) in line.char_indices() {
+        let after_space = previous.is_none_or(char::is_whitespace);
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => {
+                quote = None;
+                closed = true;
+                previous = Some(c);
+                continue;
+            },
+            None if c == '#' && (after_space || closed) => return Some(i),
+            None if matches!(c, '"' | '\'') &&
+                (after_space || previous.is_some_and(|p| "=:[{,".contains(p))) =>
+            {
+                quote = Some(c);
+            },
+            _ => {},
+        }
+
+        closed = false;
+        previous = Some(c);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Never set, so that it is substituted with the default or fails.
+    const UNSET: &str = "BOTMAID_TEST_UNSET_VAR";
+
+    #[test]
+    fn find_comment_outside_quotes() {
+        for (line, comment) in [
+            ("a = 1 # c", Some(6)),
+            ("# c", Some(0)),
+            (r#"a = "x # y" # c"#, Some(12)),
+            (r##"a = "x # y"# c"##, Some(11)),
+            ("a: 'x # y' # c", Some(11)),
+            (r#"a = "x \" # y" # c"#, Some(15)),
+            (r"a = 'x \' # y", Some(10)),
+            ("a: don't # c", Some(9)),
+            ("a: http://x/#y", None),
+            (r##"a = ["x", "#"]"##, None),
+            (r#"a = "x # y"#, None),
+        ] {
+            assert_eq!(find_comment(line), comment, "{line}");
+        }
+    }
+
+    #[test]
+    fn substitute_env_vars() {
+        assert_eq!(
+            substitute_env(&format!("a = \"${{{UNSET}:-x}}\"\nb = 1")).unwrap(),
+            "a = \"x\"\nb = 1"
+        );
+        assert_eq!(
+            substitute_env(&format!("a = \"$${{{UNSET}}}\"")).unwrap(),
+            format!("a = \"${{{UNSET}}}\"")
+        );
+
+        let err = substitute_env(&format!("a = \"${{{UNSET}}}\"")).unwrap_err();
+        assert!(format!("{err:#}").contains(UNSET), "{err:#}");
+        assert!(substitute_env("a = \"${x\"").is_err());
+    }
+
+    #[test]
+    fn substitute_env_leaves_dollars_and_comments() {
+        for s in [
+            "a = \"$5 $x $\"\n",
+            "a = \"{$}\"",
+            &format!("a = 1 # ${{{UNSET}}}\n"),
+            &format!("# ${{{UNSET}}}\nb = 2\n"),
+        ] {
+            assert_eq!(substitute_env(s).unwrap(), s);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use crate::api::BotAPI;
+use crate::{Chat, Message, User};
+
+pub struct Conversations<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    waiters: Mutex<Vec<Waiter<C>>>,
+}
+
+struct Waiter<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    api: Arc<dyn BotAPI<C>>,
+    chat_type: i32,
+    chat_id: String,
+    user_id: String,
+
+    tx: oneshot::Sender<Message<C>>,
+}
+
+impl<C> Conversations<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    pub const fn new() -> Self {
+        Self {
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn register(&self, chat: &Chat<C>, user: &User) -> Pending<C> {
+        let (tx, rx) = oneshot::channel();
+
+        self.lock().push(Waiter {
+            api: chat.get_api().clone(),
+            chat_type: chat.type_as_i32(),
+            chat_id: chat.get_id().clone(),
+            user_id: user.get_id().clone(),
+
+            tx,
+        });
+
+        Pending { rx }
+    }
+
+    /// Hands `msg` to the earliest waiter expecting it, returning it back if
+    /// nobody is waiting.
+    pub fn intercept(&self, mut msg: Message<C>) -> Option<Message<C>> {
+        let mut waiters = self.lock();
+        waiters.retain(|waiter| !waiter.tx.is_closed());
+
+        while let Some(pos) = waiters.iter().position(|waiter| waiter.expects(&msg)) {
+            match waiters.remove(pos).tx.send(msg) {
+                Ok(()) => return None,
+                Err(returned) => msg = returned,
+            }
+        }
+
+        Some(msg)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Waiter<C>>> {
+        self.waiters
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl<C> Waiter<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    fn expects(&self, msg: &Message<C>) -> bool {
+        let chat = msg.get_chat();
+
+        std::ptr::addr_eq(Arc::as_ptr(&self.api), Arc::as_ptr(chat.get_api())) &&
+            self.chat_type == chat.type_as_i32() &&
+            &self.chat_id == chat.get_id() &&
+            &self.user_id == msg.get_sender().get_id()
+    }
+}
+
+pub struct Pending<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    rx: oneshot::Receiver<Message<C>>,
+}
+
+impl<C> Pending<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    pub async fn wait(self, timeout: Duration) -> Option<Message<C>> {
+        tokio::time::timeout(timeout, self.rx)
+            .await
+            .ok()
+            .and_then(Result::ok)
+    }
+}
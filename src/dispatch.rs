@@ -0,0 +1,194 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::Event;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DispatchMode {
+    /// Every event is handled as soon as it arrives.
+    #[default]
+    Concurrent,
+    /// Events of the same chat are handled one after another, in order.
+    PerChat,
+    /// Events of the same user are handled one after another, in order.
+    PerUser,
+}
+
+impl DispatchMode {
+    pub(crate) fn key<C>(self, api_index: usize, event: &Event<C>) -> Option<String>
+    where
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        match self {
+            Self::Concurrent => None,
+            Self::PerChat => event
+                .get_chat()
+                .map(|chat| format!("{api_index}:{}:{}", chat.type_as_i32(), chat.get_id())),
+            Self::PerUser => event
+                .get_user()
+                .map(|user| format!("{api_index}:{}", user.get_id())),
+        }
+    }
+}
+
+/// FIFO queues of events waiting for the worker of their key.
+pub(crate) struct Queues<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    queues: Mutex<HashMap<String, VecDeque<Event<C>>>>,
+}
+
+impl<C> Queues<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Queues `event` behind the running worker of `key`, or hands it back if
+    /// there is none and the caller has to start one.
+    pub(crate) fn push(&self, key: &str, event: Event<C>) -> Option<Event<C>> {
+        match self.lock().entry(key.to_owned()) {
+            Entry::Occupied(mut queue) => {
+                queue.get_mut().push_back(event);
+                None
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(VecDeque::new());
+                Some(event)
+            },
+        }
+    }
+
+    /// Takes the next event for the worker of `key`, retiring the worker if
+    /// there is none.
+    pub(crate) fn pop(&self, key: &str) -> Option<Event<C>> {
+        let mut queues = self.lock();
+
+        let event = queues.get_mut(key).and_then(VecDeque::pop_front);
+        if event.is_none() {
+            queues.remove(key);
+        }
+
+        event
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, VecDeque<Event<C>>>> {
+        self.queues
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Remembers recently seen messages to drop redeliveries of them.
+pub(crate) struct Dedup {
+    window: Duration,
+    capacity: usize,
+    seen: Mutex<Seen>,
+}
+
+#[derive(Default)]
+struct Seen {
+    keys: HashSet<String>,
+    order: VecDeque<(Instant, String)>,
+}
+
+impl Dedup {
+    pub(crate) fn new(window: Duration, capacity: usize) -> Self {
+        Self {
+            window,
+            capacity,
+            seen: Mutex::new(Seen::default()),
+        }
+    }
+
+    /// Returns whether `event` is a message already seen within the window.
+    pub(crate) fn is_duplicate<C>(&self, api_index: usize, event: &Event<C>) -> bool
+    where
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        let Event::Message(msg) = event else {
+            return false;
+        };
+
+        let key = format!(
+            "{api_index}:{}:{}:{}",
+            msg.get_chat().type_as_i32(),
+            msg.get_chat().get_id(),
+            msg.get_id()
+        );
+
+        let now = Instant::now();
+        let mut seen = self
+            .seen
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        while let Some((time, _)) = seen.order.front() &&
+            now.duration_since(*time) > self.window
+        {
+            if let Some((_, expired)) = seen.order.pop_front() {
+                seen.keys.remove(&expired);
+            }
+        }
+
+        if seen.keys.contains(&key) {
+            return true;
+        }
+
+        // Evicted only after the lookup, so that the last `capacity` keys are
+        // all looked up
+        seen.keys.insert(key.clone());
+        seen.order.push_back((now, key));
+        if seen.order.len() > self.capacity &&
+            let Some((_, oldest)) = seen.order.pop_front()
+        {
+            seen.keys.remove(&oldest);
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::api::mock::Mock;
+    use crate::{Chat, Message, MessageContents, User};
+
+    fn message(api: &Arc<Mock<()>>, id: &str) -> Event<()> {
+        let user = User::new("1".to_owned());
+        Event::Message(Message::new(
+            id.to_owned(),
+            MessageContents::new(),
+            Chat::private(api.clone(), user.clone()),
+            user,
+        ))
+    }
+
+    #[test]
+    fn dedup_at_capacity() {
+        let api = Arc::new(Mock::new(()));
+        let dedup = Dedup::new(Duration::from_secs(10), 2);
+
+        assert!(!dedup.is_duplicate(0, &message(&api, "1")));
+        assert!(!dedup.is_duplicate(0, &message(&api, "2")));
+        // Both earlier messages are still remembered at the boundary
+        assert!(dedup.is_duplicate(0, &message(&api, "1")));
+        assert!(dedup.is_duplicate(0, &message(&api, "2")));
+
+        assert!(!dedup.is_duplicate(0, &message(&api, "3")));
+        assert!(!dedup.is_duplicate(0, &message(&api, "1")));
+        assert!(dedup.is_duplicate(0, &message(&api, "3")));
+        assert!(!dedup.is_duplicate(1, &message(&api, "3")));
+    }
+}
@@ -1,13 +1,45 @@
 use std::fmt::{Debug, Display};
+use std::net::SocketAddr;
 use std::ops::Deref;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use api::BotAPI;
+use config::Config;
+use conversation::Conversations;
 use derivative::Derivative;
+use dispatch::{Dedup, DispatchMode, Queues};
+use middleware::{Middleware, Next};
+use permission::Permissions;
+use recorder::Recorder;
+use scheduler::Scheduler;
+use serde::{Deserialize, Serialize};
+use shutdown::ShutdownHandle;
+use storage::{Scope, Storage};
+use text::{Arg, Mention, Tokenizer};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio::task::JoinSet;
+use tokio_util::task::TaskTracker;
 
 pub mod api;
+pub mod bridge;
+pub mod codec;
+pub mod command;
+pub mod config;
+mod conversation;
+pub mod dispatch;
+pub mod metrics;
+pub mod middleware;
+pub mod permission;
+pub mod recorder;
+pub mod scheduler;
+pub mod shutdown;
+pub mod storage;
+pub mod text;
+
+static DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[async_trait::async_trait]
 pub trait BotInstance<C>: Send + Sync + 'static
@@ -15,7 +47,19 @@ where
     C: Clone + Debug + Send + Sync + 'static,
 {
     async fn handle_msg(self: &Arc<Self>, msg: Message<C>) -> Result<()>;
-    async fn run_jobs(self: &Arc<Self>, apis: &Arc<Vec<Arc<dyn BotAPI<C>>>>) -> Result<()>;
+
+    /// Receives every event other than [`Event::Message`].
+    async fn handle_event(self: &Arc<Self>, event: Event<C>) -> Result<()> {
+        let _ = event;
+        Ok(())
+    }
+
+    /// Runs once at startup. Periodic work is better registered on the
+    /// [`Scheduler`].
+    async fn run_jobs(self: &Arc<Self>, apis: &Arc<Vec<Arc<dyn BotAPI<C>>>>) -> Result<()> {
+        let _ = apis;
+        Ok(())
+    }
 }
 
 pub struct BotMaidBuilder<I, C>
@@ -24,7 +68,23 @@ where
     C: Clone + Debug + Send + Sync + 'static,
 {
     apis: Vec<Arc<dyn BotAPI<C>>>,
+    middlewares: Vec<Arc<dyn Middleware<C>>>,
     instance: I,
+
+    dispatch_mode: DispatchMode,
+    max_concurrency: Option<usize>,
+    dedup: Option<Dedup>,
+    recorder: Option<Recorder>,
+
+    scheduler: Arc<Scheduler<C>>,
+    storage: Arc<dyn Storage>,
+    permissions: Arc<Permissions>,
+
+    metrics_addr: Option<SocketAddr>,
+
+    shutdown: ShutdownHandle,
+    shutdown_timeout: Duration,
+    handle_signals: bool,
 }
 
 impl<I, C> BotMaidBuilder<I, C>
@@ -41,10 +101,122 @@ where
         api
     }
 
+    /// Adds the APIs described by `config`, each with a clone of `context`,
+    /// and makes their configured owners owners of them in the
+    /// [`Permissions`]. Nothing is added if any of them fails to be created.
+    ///
+    /// # Errors
+    pub async fn apply_config(&mut self, config: &Config, context: C) -> Result<()> {
+        config.apply(self, &context).await
+    }
+
+    pub fn add_middleware<M>(&mut self, middleware: M) -> Arc<M>
+    where
+        M: Middleware<C>,
+    {
+        let middleware = Arc::new(middleware);
+        self.middlewares.push(middleware.clone());
+        middleware
+    }
+
+    pub const fn set_dispatch_mode(&mut self, dispatch_mode: DispatchMode) {
+        self.dispatch_mode = dispatch_mode;
+    }
+
+    /// Limits how many events are handled at the same time across all APIs.
+    ///
+    /// Further events wait to be read until a handler finishes, so a handler
+    /// waiting in a conversation keeps its slot.
+    pub const fn set_max_concurrency(&mut self, max_concurrency: usize) {
+        self.max_concurrency = Some(max_concurrency);
+    }
+
+    /// Drops messages redelivered by the platforms, recognized by their API,
+    /// chat and id among the last `capacity` messages received within
+    /// `window`.
+    pub fn set_dedup(&mut self, window: Duration, capacity: usize) {
+        self.dedup = Some(Dedup::new(window, capacity));
+    }
+
+    /// Records every event received, see [`Recorder`].
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    #[must_use]
+    pub fn scheduler(&self) -> Arc<Scheduler<C>> {
+        self.scheduler.clone()
+    }
+
+    /// Replaces the default in-memory storage.
+    pub fn set_storage<S>(&mut self, storage: S) -> Arc<S>
+    where
+        S: Storage,
+    {
+        let storage = Arc::new(storage);
+        self.storage = storage.clone();
+        storage
+    }
+
+    #[must_use]
+    pub fn permissions(&self) -> Arc<Permissions> {
+        self.permissions.clone()
+    }
+
+    /// Serves the [`metrics`] in the Prometheus text format at
+    /// `http://{addr}/metrics`.
+    pub const fn set_metrics_addr(&mut self, addr: SocketAddr) {
+        self.metrics_addr = Some(addr);
+    }
+
+    #[must_use]
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Sets how long to wait for in-flight handlers and `run_jobs` after a
+    /// shutdown is requested.
+    pub const fn set_shutdown_timeout(&mut self, timeout: Duration) {
+        self.shutdown_timeout = timeout;
+    }
+
+    /// Shuts down on SIGINT or SIGTERM.
+    pub const fn set_handle_signals(&mut self, handle_signals: bool) {
+        self.handle_signals = handle_signals;
+    }
+
     pub async fn run(self) {
+        let apis = Arc::new(self.apis);
+        let tracker = TaskTracker::new();
+
         BotMaid {
-            apis: Arc::new(self.apis),
+            apis: apis.clone(),
+            middlewares: self.middlewares,
             instance: Arc::new(self.instance),
+            runtime: Arc::new(Runtime {
+                apis,
+                conversations: Conversations::new(),
+                storage: self.storage,
+                permissions: self.permissions,
+                tracker: tracker.clone(),
+            }),
+
+            dispatch_mode: self.dispatch_mode,
+            dedup: self.dedup,
+            recorder: self.recorder,
+            queues: Queues::new(),
+            semaphore: self
+                .max_concurrency
+                .map(|max_concurrency| Arc::new(Semaphore::new(max_concurrency))),
+            tracker,
+
+            scheduler: self.scheduler,
+
+            metrics_addr: self.metrics_addr,
+
+            shutdown: self.shutdown,
+            shutdown_timeout: self.shutdown_timeout,
+            handle_signals: self.handle_signals,
         }
         .run()
         .await;
@@ -57,7 +229,24 @@ where
     C: Clone + Debug + Send + Sync + 'static,
 {
     apis: Arc<Vec<Arc<dyn BotAPI<C>>>>,
+    middlewares: Vec<Arc<dyn Middleware<C>>>,
     instance: Arc<I>,
+    runtime: Arc<Runtime<C>>,
+
+    dispatch_mode: DispatchMode,
+    dedup: Option<Dedup>,
+    recorder: Option<Recorder>,
+    queues: Queues<C>,
+    semaphore: Option<Arc<Semaphore>>,
+    tracker: TaskTracker,
+
+    scheduler: Arc<Scheduler<C>>,
+
+    metrics_addr: Option<SocketAddr>,
+
+    shutdown: ShutdownHandle,
+    shutdown_timeout: Duration,
+    handle_signals: bool,
 }
 
 impl<I, C> BotMaid<I, C>
@@ -70,16 +259,60 @@ where
     pub fn new(instance: I) -> BotMaidBuilder<I, C> {
         BotMaidBuilder {
             apis: Vec::new(),
+            middlewares: Vec::new(),
             instance,
+
+            dispatch_mode: DispatchMode::default(),
+            max_concurrency: None,
+            dedup: None,
+            recorder: None,
+
+            scheduler: Arc::new(Scheduler::new()),
+            storage: Arc::new(storage::memory::Memory::new()),
+            permissions: Arc::new(Permissions::new()),
+
+            metrics_addr: None,
+
+            shutdown: ShutdownHandle::new(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            handle_signals: false,
         }
     }
 
-    /// # Errors
+    /// Runs until shutdown is requested through the [`ShutdownHandle`] (or a
+    /// signal, if enabled), then waits up to the shutdown timeout for
+    /// in-flight handlers and `run_jobs` before returning.
     pub async fn run(self) {
         let self_arc = Arc::new(self);
 
+        if self_arc.handle_signals {
+            let shutdown = self_arc.shutdown.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    res = shutdown::wait_for_signal() => {
+                        if let Err(err) = res {
+                            tracing::error!("{err:?}");
+                            return;
+                        }
+                        tracing::info!("received shutdown signal");
+                        shutdown.shutdown();
+                    },
+                    () = shutdown.cancelled() => {},
+                }
+            });
+        }
+
+        if let Some(addr) = self_arc.metrics_addr {
+            let shutdown = self_arc.shutdown.clone();
+            tokio::spawn(async move {
+                if let Err(err) = metrics::serve(addr, shutdown).await {
+                    tracing::error!("{err:?}");
+                }
+            });
+        }
+
         let mut join_set = JoinSet::new();
-        for api in self_arc.apis.iter() {
+        for (api_index, api) in self_arc.apis.iter().enumerate() {
             let api_clone = api.clone();
             join_set.spawn(async move {
                 api_clone.run().await;
@@ -88,47 +321,279 @@ where
             let api_clone = api.clone();
             let self_clone = self_arc.clone();
             join_set.spawn(async move {
-                while let Some(event) = api_clone.next_event().await {
-                    let self_clone = self_clone.clone();
-                    tokio::spawn(async move {
-                        if let Err(err) = self_clone.handle_event(event).await {
-                            tracing::error!("{err:?}");
-                        }
-                    });
+                let api_label = metrics::api_label(api_clone.as_ref());
+                loop {
+                    let event = tokio::select! {
+                        event = api_clone.next_event() => event,
+                        () = self_clone.shutdown.cancelled() => break,
+                    };
+                    let Some(event) = event else { break };
+                    metrics::event_received(&api_label, event.get_type());
+                    if let Some(recorder) = &self_clone.recorder {
+                        recorder.record(api_clone.as_ref(), &event).await;
+                    }
+
+                    let Some(event) = self_clone.prepare_event(api_index, event) else {
+                        continue;
+                    };
+
+                    self_clone.dispatch(api_index, event).await;
                 }
             });
         }
 
+        self_arc.scheduler.start(&self_arc.runtime);
+
         let self_clone = self_arc.clone();
-        join_set.spawn(async move {
+        let mut jobs = tokio::spawn(async move {
             if let Err(err) = self_clone.instance.run_jobs(&self_clone.apis).await {
                 tracing::error!("{err:?}");
             }
         });
 
-        join_set.join_all().await;
+        self_arc.shutdown.cancelled().await;
+        tracing::info!("shutting down");
+
+        join_set.shutdown().await;
+
+        self_arc.tracker.close();
+        let drain = async {
+            self_arc.scheduler.stop().await;
+            self_arc.tracker.wait().await;
+            if let Err(err) = (&mut jobs).await {
+                tracing::error!("{err:?}");
+            }
+        };
+        if tokio::time::timeout(self_arc.shutdown_timeout, drain)
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "shutdown timed out after {:?}, abandoning {} handler(s)",
+                self_arc.shutdown_timeout,
+                self_arc.tracker.len()
+            );
+            jobs.abort();
+        }
+
+        tracing::info!("shut down");
     }
 
-    async fn handle_event(self: Arc<Self>, event: Event<C>) -> Result<()> {
-        tracing::info!("handling event: {event:?}");
+    fn prepare_event(&self, api_index: usize, mut event: Event<C>) -> Option<Event<C>> {
+        if let Some(dedup) = &self.dedup &&
+            dedup.is_duplicate(api_index, &event)
+        {
+            tracing::info!("dropping duplicate event: {event:?}");
+            return None;
+        }
+
+        if let Some(chat) = event.get_chat_mut() {
+            chat.runtime = Some(self.runtime.clone());
+        }
+
+        if let Event::Message(msg) = event {
+            let Some(msg) = self.runtime.conversations.intercept(msg) else {
+                tracing::info!("message consumed by a conversation");
+                return None;
+            };
+
+            return Some(Event::Message(msg));
+        }
+
+        Some(event)
+    }
+
+    async fn dispatch(self: &Arc<Self>, api_index: usize, event: Event<C>) {
+        let key = self.dispatch_mode.key(api_index, &event);
+
+        let event = match &key {
+            Some(key) => match self.queues.push(key, event) {
+                Some(event) => event,
+                // The running worker of this key will take it
+                None => return,
+            },
+            None => event,
+        };
+
+        // Waited for before spawning, so a flood of events is held back in
+        // the adapter instead of piling up in tasks
+        let permit = self.acquire_permit().await;
+
+        let self_clone = self.clone();
+        self.tracker.spawn(async move {
+            self_clone.handle_event(event).await;
+            drop(permit);
+
+            let Some(key) = key else { return };
+            while let Some(event) = self_clone.queues.pop(&key) {
+                let permit = self_clone.acquire_permit().await;
+                self_clone.handle_event(event).await;
+                drop(permit);
+            }
+        });
+    }
+
+    async fn acquire_permit(&self) -> Option<OwnedSemaphorePermit> {
+        // The semaphore is never closed
+        self.semaphore.clone()?.acquire_owned().await.ok()
+    }
+
+    async fn handle_event(&self, event: Event<C>) {
+        let event_type = event.get_type();
+        let start = Instant::now();
+
+        let res = self.handle_event_inner(event).await;
+        metrics::event_handled(event_type, start.elapsed(), res.is_ok());
 
-        match event {
-            Event::Message(msg) => self.instance.handle_msg(msg).await,
-            Event::Other(_) => Ok(()),
+        if let Err(err) = res {
+            tracing::error!("{err:?}");
         }
     }
+
+    async fn handle_event_inner(&self, event: Event<C>) -> Result<()> {
+        tracing::info!("handling event: {event:?}");
+
+        Next::new(&self.middlewares, &self.instance)
+            .run(event)
+            .await
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
 pub enum Event<C>
 where
     C: Clone + Debug + Send + Sync + 'static,
 {
     Message(Message<C>),
-    #[allow(dead_code)]
+    MessageEdited(Message<C>),
+    MessageRecalled {
+        chat: Chat<C>,
+        message_id: String,
+        sender: Option<User>,
+        operator: Option<User>,
+    },
+    MemberJoined {
+        chat: Chat<C>,
+        user: User,
+        operator: Option<User>,
+    },
+    MemberLeft {
+        chat: Chat<C>,
+        user: User,
+        operator: Option<User>,
+    },
+    FriendRequest {
+        chat: Chat<C>,
+        user: User,
+        comment: Option<String>,
+        /// Platform token identifying the request, if any.
+        flag: Option<String>,
+    },
+    GroupJoinRequest {
+        chat: Chat<C>,
+        user: User,
+        comment: Option<String>,
+        /// Platform token identifying the request, if any.
+        flag: Option<String>,
+    },
+    Poke {
+        chat: Chat<C>,
+        sender: User,
+        target: User,
+    },
+    BotAdded {
+        chat: Chat<C>,
+        operator: Option<User>,
+    },
+    BotRemoved {
+        chat: Chat<C>,
+        operator: Option<User>,
+    },
+    /// The connection of an API to its platform changed, see
+    /// [`BotAPI::get_health`].
+    ApiStatusChanged {
+        #[derivative(Debug = "ignore")]
+        api: Arc<dyn BotAPI<C>>,
+        previous: api::Status,
+        health: api::Health,
+    },
+    /// An event the adapter does not understand, in its raw form.
     Other(String),
 }
 
+impl<C> Event<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    #[must_use]
+    pub const fn get_chat(&self) -> Option<&Chat<C>> {
+        match self {
+            Self::Message(msg) | Self::MessageEdited(msg) => Some(&msg.chat),
+            Self::MessageRecalled { chat, .. } |
+            Self::MemberJoined { chat, .. } |
+            Self::MemberLeft { chat, .. } |
+            Self::FriendRequest { chat, .. } |
+            Self::GroupJoinRequest { chat, .. } |
+            Self::Poke { chat, .. } |
+            Self::BotAdded { chat, .. } |
+            Self::BotRemoved { chat, .. } => Some(chat),
+            Self::ApiStatusChanged { .. } | Self::Other(_) => None,
+        }
+    }
+
+    /// A short snake case name of the variant, e.g. `message_edited`.
+    #[must_use]
+    pub const fn get_type(&self) -> &'static str {
+        match self {
+            Self::Message(_) => "message",
+            Self::MessageEdited(_) => "message_edited",
+            Self::MessageRecalled { .. } => "message_recalled",
+            Self::MemberJoined { .. } => "member_joined",
+            Self::MemberLeft { .. } => "member_left",
+            Self::FriendRequest { .. } => "friend_request",
+            Self::GroupJoinRequest { .. } => "group_join_request",
+            Self::Poke { .. } => "poke",
+            Self::BotAdded { .. } => "bot_added",
+            Self::BotRemoved { .. } => "bot_removed",
+            Self::ApiStatusChanged { .. } => "api_status_changed",
+            Self::Other(_) => "other",
+        }
+    }
+
+    /// The user who caused the event, if known.
+    #[must_use]
+    pub const fn get_user(&self) -> Option<&User> {
+        match self {
+            Self::Message(msg) | Self::MessageEdited(msg) => Some(&msg.sender),
+            Self::MessageRecalled { operator, .. } |
+            Self::BotAdded { operator, .. } |
+            Self::BotRemoved { operator, .. } => operator.as_ref(),
+            Self::MemberJoined { user, .. } |
+            Self::MemberLeft { user, .. } |
+            Self::FriendRequest { user, .. } |
+            Self::GroupJoinRequest { user, .. } => Some(user),
+            Self::Poke { sender, .. } => Some(sender),
+            Self::ApiStatusChanged { .. } | Self::Other(_) => None,
+        }
+    }
+
+    const fn get_chat_mut(&mut self) -> Option<&mut Chat<C>> {
+        match self {
+            Self::Message(msg) | Self::MessageEdited(msg) => Some(&mut msg.chat),
+            Self::MessageRecalled { chat, .. } |
+            Self::MemberJoined { chat, .. } |
+            Self::MemberLeft { chat, .. } |
+            Self::FriendRequest { chat, .. } |
+            Self::GroupJoinRequest { chat, .. } |
+            Self::Poke { chat, .. } |
+            Self::BotAdded { chat, .. } |
+            Self::BotRemoved { chat, .. } => Some(chat),
+            Self::ApiStatusChanged { .. } | Self::Other(_) => None,
+        }
+    }
+}
+
 #[derive(Clone, Derivative)]
 #[derivative(Debug)]
 pub struct Message<C>
@@ -139,6 +604,7 @@ where
     contents: MessageContents,
     chat: Chat<C>,
     sender: User,
+    reply_to: Option<Box<ReplyTo>>,
 }
 
 impl<C> Message<C>
@@ -152,6 +618,16 @@ where
             contents,
             chat,
             sender,
+            reply_to: None,
+        }
+    }
+
+    /// Marks the message as a reply to another one.
+    #[must_use]
+    pub fn reply_to(self, reply_to: ReplyTo) -> Self {
+        Self {
+            reply_to: Some(Box::new(reply_to)),
+            ..self
         }
     }
 
@@ -175,6 +651,12 @@ where
         &self.contents
     }
 
+    /// The message this one replies to, if any.
+    #[must_use]
+    pub fn get_reply_to(&self) -> Option<&ReplyTo> {
+        self.reply_to.as_deref()
+    }
+
     #[must_use]
     pub fn get_api(&self) -> &Arc<dyn BotAPI<C>> {
         &self.chat.api
@@ -187,24 +669,82 @@ where
 
     #[must_use]
     pub fn be_at(&self) -> bool {
-        for content in &self.contents {
-            if let MessageContent::At(user) = content {
-                if user.get_id() == &self.get_api().get_self_user().id {
-                    return true;
-                }
-            }
-        }
+        let self_id = &self.get_api().get_self_user().id;
+
+        self.contents
+            .mentions()
+            .iter()
+            .any(|user| user.get_id() == self_id)
+    }
 
-        false
+    /// The contents without the leading mention of the bot, if it is there,
+    /// see [`Message::be_at`].
+    #[must_use]
+    pub fn strip_at(&self) -> Option<MessageContents> {
+        self.contents.strip_mention(self.get_api().get_self_user())
+    }
+
+    /// Whether the message replies to one sent by the bot.
+    #[must_use]
+    pub fn be_replied(&self) -> bool {
+        self.get_reply_to()
+            .and_then(ReplyTo::get_sender)
+            .is_some_and(|sender| sender.get_id() == &self.get_api().get_self_user().id)
     }
 
     /// # Errors
     pub async fn reply(&self, contents: MessageContents) -> Result<String> {
         self.chat.api.reply_to_msg(contents, self).await
     }
+
+    /// Like [`Message::reply`], but returns the ids of all the messages sent,
+    /// e.g. if the reply is split.
+    ///
+    /// # Errors
+    pub async fn reply_parts(&self, contents: MessageContents) -> Result<Vec<String>> {
+        tracing::info!("replying to message [{self:?}]: {contents}");
+
+        self.chat
+            .api
+            .send_parts(contents, self.chat.clone(), Some(self.get_id()))
+            .await
+    }
+
+    /// Reads the data of an image or attachment of this message, see
+    /// [`BotAPI::download`].
+    ///
+    /// # Errors
+    pub async fn download(&self, source: &MediaSource) -> Result<Vec<u8>> {
+        self.chat.api.download(source).await
+    }
+
+    /// Whether the sender holds `permission` in this chat, see
+    /// [`Permissions`].
+    ///
+    /// # Errors
+    pub async fn has_permission(&self, permission: &str) -> Result<bool> {
+        self.chat.has_permission(&self.sender, permission).await
+    }
+
+    /// Replies with `contents` and waits for the sender's next message in the
+    /// same chat, see [`Chat::wait_for`].
+    ///
+    /// # Errors
+    pub async fn ask(&self, contents: MessageContents, timeout: Duration) -> Result<Option<Self>> {
+        let pending = self
+            .chat
+            .get_runtime()?
+            .conversations
+            .register(&self.chat, &self.sender);
+        self.reply(contents).await?;
+
+        Ok(pending.wait(timeout).await)
+    }
 }
 
-#[derive(Clone, Debug)]
+/// Serializable with serde as tagged data, e.g. `[{"text": "hi"}]`, or as
+/// code with `#[serde(with = "botmaid_rs::codec")]`, see [`codec`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MessageContents(Vec<MessageContent>);
 
 impl MessageContents {
@@ -229,6 +769,263 @@ impl MessageContents {
         contents.push(MessageContent::At(user));
         Self(contents)
     }
+
+    #[must_use]
+    pub fn styled<D>(self, s: D, style: Style) -> Self
+    where
+        D: Display,
+    {
+        let mut contents = self.0;
+        contents.push(MessageContent::Styled {
+            text: s.to_string(),
+            style,
+        });
+        Self(contents)
+    }
+
+    #[must_use]
+    pub fn bold<D>(self, s: D) -> Self
+    where
+        D: Display,
+    {
+        self.styled(s, Style::Bold)
+    }
+
+    #[must_use]
+    pub fn italic<D>(self, s: D) -> Self
+    where
+        D: Display,
+    {
+        self.styled(s, Style::Italic)
+    }
+
+    #[must_use]
+    pub fn underline<D>(self, s: D) -> Self
+    where
+        D: Display,
+    {
+        self.styled(s, Style::Underline)
+    }
+
+    #[must_use]
+    pub fn strikethrough<D>(self, s: D) -> Self
+    where
+        D: Display,
+    {
+        self.styled(s, Style::Strikethrough)
+    }
+
+    #[must_use]
+    pub fn spoiler<D>(self, s: D) -> Self
+    where
+        D: Display,
+    {
+        self.styled(s, Style::Spoiler)
+    }
+
+    #[must_use]
+    pub fn code<D>(self, s: D) -> Self
+    where
+        D: Display,
+    {
+        self.styled(s, Style::Code)
+    }
+
+    #[must_use]
+    pub fn pre<D>(self, s: D, language: Option<String>) -> Self
+    where
+        D: Display,
+    {
+        self.styled(s, Style::Pre { language })
+    }
+
+    #[must_use]
+    pub fn link<D>(self, s: D, url: String) -> Self
+    where
+        D: Display,
+    {
+        self.styled(s, Style::Link { url })
+    }
+
+    #[must_use]
+    pub fn image(self, source: MediaSource) -> Self {
+        let mut contents = self.0;
+        contents.push(MessageContent::Image(source));
+        Self(contents)
+    }
+
+    #[must_use]
+    pub fn voice(self, attachment: Attachment) -> Self {
+        let mut contents = self.0;
+        contents.push(MessageContent::Voice(attachment));
+        Self(contents)
+    }
+
+    #[must_use]
+    pub fn video(self, attachment: Attachment) -> Self {
+        let mut contents = self.0;
+        contents.push(MessageContent::Video(attachment));
+        Self(contents)
+    }
+
+    #[must_use]
+    pub fn file(self, attachment: Attachment) -> Self {
+        let mut contents = self.0;
+        contents.push(MessageContent::File(attachment));
+        Self(contents)
+    }
+
+    #[must_use]
+    pub fn forward(self, nodes: Vec<ForwardNode>) -> Self {
+        let mut contents = self.0;
+        contents.push(MessageContent::Forward(nodes));
+        Self(contents)
+    }
+
+    /// Splits the contents into parts of at most `max_length` UTF-16 code
+    /// units, at line breaks or else at whitespace. Mentions, forwards and
+    /// styled text are kept whole, unless a styled text is too long by itself.
+    ///
+    /// There is always at least one part.
+    #[must_use]
+    pub fn split(self, max_length: usize) -> Vec<Self> {
+        text::split(self.0, max_length)
+    }
+
+    /// The text without formatting, media or forwards. Mentions are rendered
+    /// as `mention` says, as words of their own.
+    #[must_use]
+    pub fn plain_text(&self, mention: Mention) -> String {
+        let mut text = String::new();
+        // Whether the next text needs a space to be apart from a mention
+        let mut after_mention = false;
+
+        for content in &self.0 {
+            match content {
+                MessageContent::Text(t) | MessageContent::Styled { text: t, .. } => {
+                    if after_mention && !t.starts_with(char::is_whitespace) {
+                        text.push(' ');
+                    }
+                    text.push_str(t);
+                    after_mention = false;
+                },
+                MessageContent::At(user) => {
+                    let Some(rendered) = mention.render(user) else {
+                        continue;
+                    };
+                    if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+                        text.push(' ');
+                    }
+                    text.push_str(&rendered);
+                    after_mention = true;
+                },
+                MessageContent::Image(_) |
+                MessageContent::Voice(_) |
+                MessageContent::Video(_) |
+                MessageContent::File(_) |
+                MessageContent::Forward(_) => {},
+            }
+        }
+
+        text
+    }
+
+    /// All the users mentioned, in order.
+    #[must_use]
+    pub fn mentions(&self) -> Vec<&User> {
+        self.0
+            .iter()
+            .filter_map(|content| match content {
+                MessageContent::At(user) => Some(user),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the rest of the contents if they start with a mention of
+    /// `user`, ignoring whitespace around it.
+    #[must_use]
+    pub fn strip_mention(&self, user: &User) -> Option<Self> {
+        let mut contents = self.0.iter().skip_while(
+            |content| matches!(content, MessageContent::Text(text) if text.trim().is_empty()),
+        );
+
+        match contents.next() {
+            Some(MessageContent::At(at)) if at.get_id() == user.get_id() => {
+                Some(Self(contents.cloned().collect()).trim_start())
+            },
+            _ => None,
+        }
+    }
+
+    /// Removes whitespace from both ends of the text.
+    #[must_use]
+    pub fn trim(self) -> Self {
+        self.trim_start().trim_end()
+    }
+
+    #[must_use]
+    pub fn trim_start(self) -> Self {
+        let mut contents: Vec<_> = self
+            .0
+            .into_iter()
+            .skip_while(
+                |content| matches!(content, MessageContent::Text(text) if text.trim().is_empty()),
+            )
+            .collect();
+
+        if let Some(MessageContent::Text(text)) = contents.first_mut() {
+            *text = text.trim_start().to_owned();
+        }
+
+        Self(contents)
+    }
+
+    #[must_use]
+    pub fn trim_end(self) -> Self {
+        let mut contents = self.0;
+
+        while let Some(MessageContent::Text(text)) = contents.last() &&
+            text.trim().is_empty()
+        {
+            contents.pop();
+        }
+
+        if let Some(MessageContent::Text(text)) = contents.last_mut() {
+            *text = text.trim_end().to_owned();
+        }
+
+        Self(contents)
+    }
+
+    /// Splits the contents into arguments like a shell, with single and
+    /// double quotes and backslash escapes. Text and styled text are split
+    /// together, while any other content is an argument by itself.
+    ///
+    /// A quote left open, including by a content other than text, is taken
+    /// literally, as commands are split by the
+    /// [`Router`](crate::command::Router).
+    #[must_use]
+    pub fn args(&self) -> Vec<Arg> {
+        let mut args = Vec::new();
+        let mut tokenizer = Tokenizer::default();
+
+        for content in &self.0 {
+            match content {
+                MessageContent::Text(text) | MessageContent::Styled { text, .. } => {
+                    tokenizer.feed(text);
+                },
+                content => {
+                    args.extend(tokenizer.take().into_iter().map(Arg::Text));
+                    args.push(Arg::Content(content.clone()));
+                },
+            }
+        }
+
+        args.extend(tokenizer.take().into_iter().map(Arg::Text));
+
+        args
+    }
 }
 
 impl Default for MessageContents {
@@ -275,21 +1072,322 @@ impl<'a> IntoIterator for &'a MessageContents {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MessageContent {
     Text(String),
     At(User),
+    /// Formatted text, rendered as plain text where the platform has no
+    /// formatting.
+    Styled {
+        text: String,
+        style: Style,
+    },
+    Image(MediaSource),
+    Voice(Attachment),
+    Video(Attachment),
+    File(Attachment),
+    /// A bundle of messages, sent as a merged forward where the platform has
+    /// one and as a quoted block elsewhere.
+    Forward(Vec<ForwardNode>),
 }
 
 impl Display for MessageContent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Text(text) => write!(f, "{text}"),
+            Self::Text(text) |
+            Self::Styled {
+                text,
+                style:
+                    Style::Bold |
+                    Style::Italic |
+                    Style::Underline |
+                    Style::Strikethrough |
+                    Style::Spoiler |
+                    Style::Code,
+            } => write!(f, "{text}"),
             Self::At(user) => write!(f, "@{}({}) ", user.get_nickname(), user.get_id()),
+            Self::Styled {
+                text,
+                style: Style::Pre { .. },
+            } => write!(f, "\n{text}\n"),
+            Self::Styled {
+                text,
+                style: Style::Link { url },
+            } => {
+                if text == url {
+                    write!(f, "{text}")
+                } else {
+                    write!(f, "{text} ({url})")
+                }
+            },
+            Self::Image(source) => write!(f, "[image: {source}]"),
+            Self::Voice(attachment) => write!(f, "[voice: {attachment}]"),
+            Self::Video(attachment) => write!(f, "[video: {attachment}]"),
+            Self::File(attachment) => write!(f, "[file: {attachment}]"),
+            Self::Forward(nodes) => {
+                write!(f, "\n[forward]")?;
+                for node in nodes {
+                    write!(f, "\n{node}")?;
+                }
+                writeln!(f)
+            },
+        }
+    }
+}
+
+/// A message in a [`MessageContent::Forward`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ForwardNode {
+    sender: User,
+    contents: MessageContents,
+}
+
+impl ForwardNode {
+    #[must_use]
+    pub const fn new(sender: User, contents: MessageContents) -> Self {
+        Self { sender, contents }
+    }
+
+    #[must_use]
+    pub const fn get_sender(&self) -> &User {
+        &self.sender
+    }
+
+    /// The nickname of the sender, or their id if they have none.
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        match self.sender.get_nickname() {
+            "" => self.sender.get_id(),
+            nickname => nickname,
+        }
+    }
+
+    #[must_use]
+    pub const fn get_contents(&self) -> &MessageContents {
+        &self.contents
+    }
+}
+
+impl Display for ForwardNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.get_name(), self.contents)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Style {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+    Spoiler,
+    /// Inline code.
+    Code,
+    /// A block of code, optionally in `language`.
+    Pre {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+    },
+    Link {
+        url: String,
+    },
+}
+
+/// Where the data of a media content comes from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaSource {
+    Url(String),
+    Path(PathBuf),
+    Bytes(#[serde(with = "codec::bytes")] Vec<u8>),
+    /// A file already on the platform, only meaningful to the API that
+    /// received it.
+    Id(String),
+}
+
+impl MediaSource {
+    /// Reads the data, downloading it if it is a URL.
+    ///
+    /// # Errors
+    ///
+    /// Fails on [`MediaSource::Id`], which only its API can resolve, see
+    /// [`Message::download`].
+    pub async fn read(&self) -> Result<Vec<u8>> {
+        match self {
+            Self::Url(url) => Ok(reqwest::get(url)
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .with_context(|| format!("failed to download {url}"))?
+                .bytes()
+                .await
+                .with_context(|| format!("failed to download {url}"))?
+                .to_vec()),
+            Self::Path(path) => tokio::fs::read(path)
+                .await
+                .with_context(|| format!("failed to read {}", path.display())),
+            Self::Bytes(bytes) => Ok(bytes.clone()),
+            Self::Id(id) => anyhow::bail!("file `{id}` can only be read through its api"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    source: MediaSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mime_type: Option<String>,
+}
+
+impl Attachment {
+    #[must_use]
+    pub const fn new(source: MediaSource) -> Self {
+        Self {
+            source,
+            name: None,
+            mime_type: None,
+        }
+    }
+
+    /// Sets the file name shown to the receivers.
+    #[must_use]
+    pub fn name(self, name: String) -> Self {
+        Self {
+            name: Some(name),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn mime_type(self, mime_type: String) -> Self {
+        Self {
+            mime_type: Some(mime_type),
+            ..self
+        }
+    }
+
+    /// Incoming attachments are usually [`MediaSource::Id`]s, read them with
+    /// [`Message::download`].
+    #[must_use]
+    pub const fn get_source(&self) -> &MediaSource {
+        &self.source
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    #[must_use]
+    pub fn get_mime_type(&self) -> Option<&str> {
+        self.mime_type.as_deref()
+    }
+}
+
+/// A reference to the message replied to.
+///
+/// Platforms do not always tell who sent the quoted message and what it
+/// said, only its id is guaranteed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplyTo {
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sender: Option<User>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    contents: Option<MessageContents>,
+}
+
+impl ReplyTo {
+    #[must_use]
+    pub const fn new(id: String) -> Self {
+        Self {
+            id,
+            sender: None,
+            contents: None,
+        }
+    }
+
+    #[must_use]
+    pub fn sender(self, sender: User) -> Self {
+        Self {
+            sender: Some(sender),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn contents(self, contents: MessageContents) -> Self {
+        Self {
+            contents: Some(contents),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    #[must_use]
+    pub const fn get_sender(&self) -> Option<&User> {
+        self.sender.as_ref()
+    }
+
+    #[must_use]
+    pub const fn get_contents(&self) -> Option<&MessageContents> {
+        self.contents.as_ref()
+    }
+}
+
+impl<C> From<Message<C>> for ReplyTo
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    fn from(msg: Message<C>) -> Self {
+        Self {
+            id: msg.id,
+            sender: Some(msg.sender),
+            contents: Some(msg.contents),
+        }
+    }
+}
+
+impl Display for Attachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{}", self.source),
+        }
+    }
+}
+
+impl Display for MediaSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Url(url) => write!(f, "{url}"),
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Bytes(bytes) => write!(f, "{} bytes", bytes.len()),
+            Self::Id(id) => write!(f, "{id}"),
         }
     }
 }
 
+/// State of a running [`BotMaid`] that chats dispatched by it can reach.
+struct Runtime<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    apis: Arc<Vec<Arc<dyn BotAPI<C>>>>,
+    conversations: Conversations<C>,
+    storage: Arc<dyn Storage>,
+    permissions: Arc<Permissions>,
+    /// Waited for on shutdown, like the handlers.
+    tracker: TaskTracker,
+}
+
 #[derive(Clone, Derivative)]
 #[derivative(Debug)]
 pub struct Chat<C>
@@ -300,6 +1398,9 @@ where
 
     #[derivative(Debug = "ignore")]
     api: Arc<dyn BotAPI<C>>,
+
+    #[derivative(Debug = "ignore")]
+    runtime: Option<Arc<Runtime<C>>>,
 }
 
 impl<C> Chat<C>
@@ -311,6 +1412,7 @@ where
         Self {
             info: ChatInfo::Private(user),
             api,
+            runtime: None,
         }
     }
 
@@ -319,19 +1421,28 @@ where
         Self {
             info: ChatInfo::Group(group),
             api,
+            runtime: None,
         }
     }
 
     #[must_use]
     pub fn spawn_private(&self, user: User) -> Self {
-        Self::private(self.api.clone(), user)
+        Self {
+            runtime: self.runtime.clone(),
+            ..Self::private(self.api.clone(), user)
+        }
     }
 
     #[must_use]
     pub fn spawn_group(&self, group: Group) -> Self {
-        Self::group(self.api.clone(), group)
+        Self {
+            runtime: self.runtime.clone(),
+            ..Self::group(self.api.clone(), group)
+        }
     }
 
+    /// The chat has no [`storage`](Self::storage) or permissions, see
+    /// [`JobContext::chat`](scheduler::JobContext::chat) for scheduled jobs.
     #[must_use]
     pub const fn from_raw(api: Arc<dyn BotAPI<C>>, chat_type: i32, chat_id: String) -> Self {
         Self {
@@ -340,6 +1451,15 @@ where
                 _ => ChatInfo::Private(User::new(chat_id)),
             },
             api,
+            runtime: None,
+        }
+    }
+
+    pub(crate) const fn from_info(api: Arc<dyn BotAPI<C>>, info: ChatInfo) -> Self {
+        Self {
+            info,
+            api,
+            runtime: None,
         }
     }
 
@@ -384,21 +1504,133 @@ where
         matches!(self.info, ChatInfo::Group(_))
     }
 
+    /// Whether both refer to the same chat of the same API.
+    #[must_use]
+    pub fn is_same(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(Arc::as_ptr(&self.api), Arc::as_ptr(&other.api)) &&
+            self.type_as_i32() == other.type_as_i32() &&
+            self.get_id() == other.get_id()
+    }
+
     /// # Errors
     pub async fn send_msg(&self, contents: MessageContents) -> Result<String> {
         self.api.send_msg(contents, self.clone()).await
     }
+
+    /// Like [`Chat::send_msg`], but returns the ids of all the messages sent,
+    /// e.g. if the message is split.
+    ///
+    /// # Errors
+    pub async fn send_msg_parts(&self, contents: MessageContents) -> Result<Vec<String>> {
+        tracing::info!("sending message to [{self:?}]: {contents}");
+
+        self.api.send_parts(contents, self.clone(), None).await
+    }
+
+    /// Replies to a message of this chat known only by its id, and returns
+    /// the ids of all the messages sent, see [`Message::reply_parts`].
+    ///
+    /// # Errors
+    pub async fn reply_to_id(
+        &self,
+        message_id: &str,
+        contents: MessageContents,
+    ) -> Result<Vec<String>> {
+        tracing::info!("replying to message `{message_id}` in [{self:?}]: {contents}");
+
+        self.api
+            .send_parts(contents, self.clone(), Some(message_id))
+            .await
+    }
+
+    /// Waits for the next message from `user` in this chat. The message is
+    /// handed to the caller instead of being dispatched to `handle_msg`.
+    ///
+    /// Returns `None` on timeout.
+    ///
+    /// # Errors
+    pub async fn wait_for(&self, user: &User, timeout: Duration) -> Result<Option<Message<C>>> {
+        Ok(self
+            .get_runtime()?
+            .conversations
+            .register(self, user)
+            .wait(timeout)
+            .await)
+    }
+
+    /// Data kept for this chat across restarts, see [`storage`].
+    ///
+    /// # Errors
+    pub fn storage(&self) -> Result<Scope> {
+        Ok(Scope::new(
+            self.get_runtime()?.storage.clone(),
+            format!(
+                "{}/chat/{}/{}",
+                self.api.get_self_user().get_id(),
+                self.type_as_i32(),
+                self.get_id()
+            ),
+        ))
+    }
+
+    /// Data kept for `user` across restarts. It is shared by all the chats of
+    /// the same API.
+    ///
+    /// # Errors
+    pub fn user_storage(&self, user: &User) -> Result<Scope> {
+        Ok(Scope::new(
+            self.get_runtime()?.storage.clone(),
+            format!(
+                "{}/user/{}",
+                self.api.get_self_user().get_id(),
+                user.get_id()
+            ),
+        ))
+    }
+
+    /// All the APIs of the running botmaid, e.g. to report their
+    /// [`BotAPI::get_health`] from an admin command.
+    ///
+    /// # Errors
+    pub fn get_apis(&self) -> Result<&[Arc<dyn BotAPI<C>>]> {
+        Ok(self.get_runtime()?.apis.as_slice())
+    }
+
+    /// # Errors
+    pub async fn has_permission(&self, user: &User, permission: &str) -> Result<bool> {
+        self.get_runtime()?
+            .permissions
+            .check(self, user, permission)
+            .await
+    }
+
+    /// The index of the API in the running botmaid, in the order added.
+    pub(crate) fn get_api_index(&self) -> Option<usize> {
+        self.runtime
+            .as_ref()?
+            .apis
+            .iter()
+            .position(|api| std::ptr::addr_eq(Arc::as_ptr(api), Arc::as_ptr(&self.api)))
+    }
+
+    fn get_runtime(&self) -> Result<&Arc<Runtime<C>>> {
+        self.runtime
+            .as_ref()
+            .context("chat is not dispatched by a running botmaid")
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ChatInfo {
     Private(User),
     Group(Group),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct User {
     id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     nickname: Option<String>,
 }
 
@@ -438,7 +1670,7 @@ impl User {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Group {
     id: String,
 }
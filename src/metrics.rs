@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Write};
+use std::net::SocketAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::api::BotAPI;
+use crate::shutdown::ShutdownHandle;
+
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+static BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(|| Registry {
+    events: Counter::new(
+        "botmaid_events_total",
+        "Events received from the APIs.",
+        &["api", "type"],
+    ),
+    handler_duration: Histogram::new(
+        "botmaid_handler_duration_seconds",
+        "Time spent handling an event.",
+        &["type"],
+    ),
+    handler_errors: Counter::new(
+        "botmaid_handler_errors_total",
+        "Events whose handling failed.",
+        &["type"],
+    ),
+    messages_sent: Counter::new(
+        "botmaid_messages_sent_total",
+        "Messages sent through the APIs.",
+        &["api", "result"],
+    ),
+    api_duration: Histogram::new(
+        "botmaid_api_request_duration_seconds",
+        "Latency of the requests made by the APIs to their platforms.",
+        &["api", "method", "result"],
+    ),
+});
+
+struct Registry {
+    events: Counter,
+    handler_duration: Histogram,
+    handler_errors: Counter,
+    messages_sent: Counter,
+    api_duration: Histogram,
+}
+
+/// Renders all the metrics collected so far in the Prometheus text format, for
+/// exposing them some other way than [`BotMaidBuilder::set_metrics_addr`](crate::BotMaidBuilder::set_metrics_addr).
+#[must_use]
+pub fn render() -> String {
+    let mut out = String::new();
+
+    REGISTRY.events.render(&mut out);
+    REGISTRY.handler_duration.render(&mut out);
+    REGISTRY.handler_errors.render(&mut out);
+    REGISTRY.messages_sent.render(&mut out);
+    REGISTRY.api_duration.render(&mut out);
+
+    out
+}
+
+pub(crate) fn api_label<C, A>(api: &A) -> String
+where
+    C: Clone + Debug + Send + Sync + 'static,
+    A: BotAPI<C> + ?Sized,
+{
+    format!("{}:{}", api.get_platform(), api.get_self_user().get_id())
+}
+
+pub(crate) fn event_received(api: &str, event_type: &str) {
+    REGISTRY.events.inc(&[api, event_type]);
+}
+
+pub(crate) fn event_handled(event_type: &str, duration: Duration, ok: bool) {
+    REGISTRY.handler_duration.observe(&[event_type], duration);
+    if !ok {
+        REGISTRY.handler_errors.inc(&[event_type]);
+    }
+}
+
+pub(crate) fn message_sent(api: &str, ok: bool) {
+    REGISTRY.messages_sent.inc(&[api, result(ok)]);
+}
+
+pub(crate) fn api_called(api: &str, method: &str, duration: Duration, ok: bool) {
+    REGISTRY
+        .api_duration
+        .observe(&[api, method, result(ok)], duration);
+}
+
+const fn result(ok: bool) -> &'static str {
+    if ok { "ok" } else { "error" }
+}
+
+/// Serves [`render`] at `/metrics` on `addr` until shutdown.
+pub(crate) async fn serve(addr: SocketAddr, shutdown: ShutdownHandle) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to bind metrics endpoint to {addr}"))?;
+    tracing::info!("serving metrics at http://{addr}/metrics");
+
+    loop {
+        let res = tokio::select! {
+            res = listener.accept() => res,
+            () = shutdown.cancelled() => return Ok(()),
+        };
+        let (stream, peer) = match res {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                // E.g. out of file descriptors, which passes once some are closed
+                tracing::error!("failed to accept metrics connection: {err:?}");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            },
+        };
+
+        tokio::spawn(async move {
+            if let Err(err) = respond(stream).await {
+                tracing::warn!("failed to serve metrics to {peer}: {err:?}");
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream) -> Result<()> {
+    let request = tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream))
+        .await
+        .context("timed out reading the request")??;
+
+    let (status, content_type, body) =
+        match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+            ["GET", "/metrics"] => ("200 OK", "text/plain; version=0.0.4", render()),
+            _ => ("404 Not Found", "text/plain", "not found\n".to_owned()),
+        };
+
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Reads up to the end of the request header.
+async fn read_request(stream: &mut TcpStream) -> Result<String> {
+    let mut buf = vec![0; 1024];
+    let mut len = 0;
+    while !buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+        if len == buf.len() {
+            anyhow::bail!("request header too large");
+        }
+
+        let n = stream.read(&mut buf[len..]).await?;
+        if n == 0 {
+            anyhow::bail!("connection closed before the request ended");
+        }
+        len += n;
+    }
+
+    Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+struct Counter {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl Counter {
+    const fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn inc(&self, label_values: &[&str]) {
+        *lock(&self.values)
+            .entry(to_owned(label_values))
+            .or_default() += 1;
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        for (label_values, value) in lock(&self.values).iter() {
+            let _ = writeln!(
+                out,
+                "{}{{{}}} {value}",
+                self.name,
+                render_labels(self.labels, label_values, None)
+            );
+        }
+    }
+}
+
+struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, HistogramValue>>,
+}
+
+#[derive(Default)]
+struct HistogramValue {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    const fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn observe(&self, label_values: &[&str], duration: Duration) {
+        let seconds = duration.as_secs_f64();
+
+        let mut values = lock(&self.values);
+        let value = values.entry(to_owned(label_values)).or_default();
+        for (bucket, bound) in value.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        value.sum += seconds;
+        value.count += 1;
+        drop(values);
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+        for (label_values, value) in lock(&self.values).iter() {
+            for (bucket, bound) in value.buckets.iter().zip(BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{{}}} {bucket}",
+                    self.name,
+                    render_labels(self.labels, label_values, Some(&bound.to_string()))
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}}} {}",
+                self.name,
+                render_labels(self.labels, label_values, Some("+Inf")),
+                value.count
+            );
+
+            let labels = render_labels(self.labels, label_values, None);
+            let _ = writeln!(out, "{}_sum{{{labels}}} {}", self.name, value.sum);
+            let _ = writeln!(out, "{}_count{{{labels}}} {}", self.name, value.count);
+        }
+    }
+}
+
+fn render_labels(labels: &[&str], label_values: &[String], le: Option<&str>) -> String {
+    labels
+        .iter()
+        .zip(label_values)
+        .map(|(label, value)| (*label, value.as_str()))
+        .chain(le.map(|le| ("le", le)))
+        .map(|(label, value)| {
+            format!(
+                "{label}=\"{}\"",
+                value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn to_owned(label_values: &[&str]) -> Vec<String> {
+    label_values.iter().map(|&value| value.to_owned()).collect()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
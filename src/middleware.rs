@@ -0,0 +1,103 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::{BotInstance, Event};
+
+/// A layer around event dispatching, configured by
+/// [`BotMaidBuilder::add_middleware`](crate::BotMaidBuilder::add_middleware).
+///
+/// Middlewares run in the order they are added. Each one decides whether to
+/// pass the (possibly modified) event on by calling [`Next::run`], or to
+/// short-circuit by returning without calling it.
+#[async_trait::async_trait]
+pub trait Middleware<C>: Send + Sync + 'static
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    async fn handle(&self, event: Event<C>, next: Next<'_, C>) -> Result<()>;
+}
+
+pub struct Next<'a, C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    middlewares: &'a [Arc<dyn Middleware<C>>],
+    endpoint: &'a dyn Endpoint<C>,
+}
+
+impl<'a, C> Next<'a, C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    pub(crate) const fn new(
+        middlewares: &'a [Arc<dyn Middleware<C>>],
+        endpoint: &'a dyn Endpoint<C>,
+    ) -> Self {
+        Self {
+            middlewares,
+            endpoint,
+        }
+    }
+
+    /// # Errors
+    pub async fn run(self, event: Event<C>) -> Result<()> {
+        if let Some((middleware, middlewares)) = self.middlewares.split_first() {
+            middleware
+                .handle(event, Self::new(middlewares, self.endpoint))
+                .await
+        } else {
+            self.endpoint.call(event).await
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub(crate) trait Endpoint<C>: Send + Sync
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    async fn call(&self, event: Event<C>) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<I, C> Endpoint<C> for Arc<I>
+where
+    I: BotInstance<C>,
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    async fn call(&self, event: Event<C>) -> Result<()> {
+        match event {
+            Event::Message(msg) => self.handle_msg(msg).await,
+            event => self.handle_event(event).await,
+        }
+    }
+}
+
+/// Drops every event for which the predicate returns `false`, e.g. to
+/// implement chat allowlists or user bans.
+pub struct Filter<F>(F);
+
+impl<F> Filter<F> {
+    #[must_use]
+    pub const fn new(predicate: F) -> Self {
+        Self(predicate)
+    }
+}
+
+#[async_trait::async_trait]
+impl<C, F> Middleware<C> for Filter<F>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+    F: Fn(&Event<C>) -> bool + Send + Sync + 'static,
+{
+    async fn handle(&self, event: Event<C>, next: Next<'_, C>) -> Result<()> {
+        if (self.0)(&event) {
+            next.run(event).await
+        } else {
+            tracing::info!("event filtered out: {event:?}");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+use anyhow::Result;
+
+use crate::{Chat, ChatInfo, User};
+
+/// Grants every permission when given to a role.
+pub static ANY_PERMISSION: &str = "*";
+
+/// Decides what users may do, on top of what the platforms report.
+///
+/// Owners may do anything. Other users hold the permissions of the roles
+/// granted to them, plus those of the group admin role while they are admins
+/// of the group at hand. Users are identified within a [`UserScope`], i.e. an
+/// API or, where wanted, all the APIs of a platform.
+///
+/// Obtained from [`BotMaidBuilder::permissions`](crate::BotMaidBuilder::permissions)
+/// and checked with [`Chat::has_permission`] or [`Message::has_permission`](crate::Message::has_permission).
+pub struct Permissions {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    owners: HashSet<(UserScope, String)>,
+    roles: HashMap<String, HashSet<String>>,
+    grants: HashMap<(UserScope, String), HashSet<String>>,
+    group_admin_role: Option<String>,
+}
+
+/// Where a user id identifies a user.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UserScope {
+    /// The API at this index, in the order the APIs are added to the
+    /// [`BotMaidBuilder`](crate::BotMaidBuilder).
+    Api(usize),
+    /// All the APIs of a platform, see
+    /// [`BotAPI::get_platform`](crate::api::BotAPI::get_platform). Only for
+    /// platforms where the same id is the same user across APIs.
+    Platform(String),
+}
+
+impl From<usize> for UserScope {
+    fn from(api_index: usize) -> Self {
+        Self::Api(api_index)
+    }
+}
+
+impl From<&str> for UserScope {
+    fn from(platform: &str) -> Self {
+        Self::Platform(platform.to_owned())
+    }
+}
+
+impl Permissions {
+    pub(crate) fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    pub fn add_owner<S>(&self, scope: S, user_id: &str)
+    where
+        S: Into<UserScope>,
+    {
+        self.lock()
+            .owners
+            .insert((scope.into(), user_id.to_owned()));
+    }
+
+    /// Adds `permissions` to `role`, creating it if needed.
+    pub fn add_role(&self, role: &str, permissions: &[&str]) {
+        self.lock()
+            .roles
+            .entry(role.to_owned())
+            .or_default()
+            .extend(permissions.iter().map(|&permission| permission.to_owned()));
+    }
+
+    pub fn grant_role<S>(&self, scope: S, user_id: &str, role: &str)
+    where
+        S: Into<UserScope>,
+    {
+        self.lock()
+            .grants
+            .entry((scope.into(), user_id.to_owned()))
+            .or_default()
+            .insert(role.to_owned());
+    }
+
+    /// Returns whether the user had the role in `scope`.
+    pub fn revoke_role<S>(&self, scope: S, user_id: &str, role: &str) -> bool
+    where
+        S: Into<UserScope>,
+    {
+        self.lock()
+            .grants
+            .get_mut(&(scope.into(), user_id.to_owned()))
+            .is_some_and(|roles| roles.remove(role))
+    }
+
+    /// Gives `role` to the admins of a group while in that group, as reported
+    /// by [`BotAPI::is_group_admin`](crate::api::BotAPI::is_group_admin).
+    pub fn set_group_admin_role(&self, role: &str) {
+        self.lock().group_admin_role = Some(role.to_owned());
+    }
+
+    /// Whether the user is an owner in exactly `scope`.
+    #[must_use]
+    pub fn is_owner<S>(&self, scope: S, user_id: &str) -> bool
+    where
+        S: Into<UserScope>,
+    {
+        self.lock()
+            .owners
+            .contains(&(scope.into(), user_id.to_owned()))
+    }
+
+    /// # Errors
+    pub async fn check<C>(&self, chat: &Chat<C>, user: &User, permission: &str) -> Result<bool>
+    where
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        let keys: Vec<_> = chat
+            .get_api_index()
+            .map(UserScope::Api)
+            .into_iter()
+            .chain([UserScope::from(chat.get_api().get_platform())])
+            .map(|scope| (scope, user.get_id().clone()))
+            .collect();
+
+        let admin_would_grant = {
+            let inner = self.lock();
+
+            if keys.iter().any(|key| inner.owners.contains(key)) {
+                return Ok(true);
+            }
+
+            let granted = keys
+                .iter()
+                .filter_map(|key| inner.grants.get(key))
+                .flatten()
+                .any(|role| inner.role_has(role, permission));
+            if granted {
+                return Ok(true);
+            }
+
+            inner
+                .group_admin_role
+                .as_ref()
+                .is_some_and(|role| inner.role_has(role, permission))
+        };
+
+        // The platform is only asked when being an admin would make a
+        // difference, and a failed answer counts as not being one.
+        Ok(match (admin_would_grant, chat.get_info()) {
+            (true, ChatInfo::Group(group)) => chat
+                .get_api()
+                .is_group_admin(user, group)
+                .await
+                .unwrap_or_else(|err| {
+                    tracing::warn!(
+                        "failed to check whether `{}` is an admin: {err:?}",
+                        user.get_id()
+                    );
+                    false
+                }),
+            _ => false,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl Inner {
+    fn role_has(&self, role: &str, permission: &str) -> bool {
+        self.roles.get(role).is_some_and(|permissions| {
+            permissions.contains(permission) || permissions.contains(ANY_PERMISSION)
+        })
+    }
+}
@@ -0,0 +1,374 @@
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::api::BotAPI;
+use crate::{Chat, ChatInfo, Event, Message, MessageContents, ReplyTo, User};
+
+/// Appends every event received by a [`BotMaid`](crate::BotMaid) to a JSON
+/// Lines file, one [`Record`] per line, to be fed back with
+/// [`Replay`](crate::api::replay::Replay).
+///
+/// [`Event::ApiStatusChanged`] is not recorded since it does not come from the
+/// platforms.
+pub struct Recorder {
+    file: Mutex<tokio::fs::File>,
+}
+
+impl Recorder {
+    /// Opens `path` for appending, creating it if it does not exist yet.
+    ///
+    /// # Errors
+    pub fn create<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+
+        Ok(Self {
+            file: Mutex::new(tokio::fs::File::from_std(file)),
+        })
+    }
+
+    pub(crate) async fn record<C, A>(&self, api: &A, event: &Event<C>)
+    where
+        C: Clone + Debug + Send + Sync + 'static,
+        A: BotAPI<C> + ?Sized,
+    {
+        let Some(event) = RecordedEvent::from_event(event) else {
+            return;
+        };
+
+        let record = Record {
+            time: Utc::now(),
+            platform: api.get_platform().to_owned(),
+            self_id: api.get_self_user().get_id().clone(),
+            event,
+        };
+
+        if let Err(err) = self.write(&record).await {
+            tracing::error!("failed to record event: {err:?}");
+        }
+    }
+
+    async fn write(&self, record: &Record) -> Result<()> {
+        let mut line = serde_json::to_vec(record).context("failed to serialize record")?;
+        line.push(b'\n');
+
+        // Written in one go so that concurrent records never interleave.
+        let mut file = self.file.lock().await;
+        file.write_all(&line)
+            .await
+            .context("failed to write record")?;
+        file.flush().await.context("failed to flush record")
+    }
+}
+
+/// An event as received by an API at some time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+    time: DateTime<Utc>,
+    platform: String,
+    self_id: String,
+    event: RecordedEvent,
+}
+
+impl Record {
+    #[must_use]
+    pub const fn get_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    /// The [`BotAPI::get_platform`] of the API that received the event.
+    #[must_use]
+    pub fn get_platform(&self) -> &str {
+        &self.platform
+    }
+
+    #[must_use]
+    pub const fn get_self_id(&self) -> &String {
+        &self.self_id
+    }
+
+    /// Rebuilds the event as if received by `api`.
+    pub(crate) fn to_event<C>(&self, api: &Arc<dyn BotAPI<C>>) -> Event<C>
+    where
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        self.event.clone().into_event(api)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RecordedEvent {
+    Message(RecordedMessage),
+    MessageEdited(RecordedMessage),
+    MessageRecalled {
+        chat: ChatInfo,
+        message_id: String,
+        sender: Option<User>,
+        operator: Option<User>,
+    },
+    MemberJoined {
+        chat: ChatInfo,
+        user: User,
+        operator: Option<User>,
+    },
+    MemberLeft {
+        chat: ChatInfo,
+        user: User,
+        operator: Option<User>,
+    },
+    FriendRequest {
+        chat: ChatInfo,
+        user: User,
+        comment: Option<String>,
+        flag: Option<String>,
+    },
+    GroupJoinRequest {
+        chat: ChatInfo,
+        user: User,
+        comment: Option<String>,
+        flag: Option<String>,
+    },
+    Poke {
+        chat: ChatInfo,
+        sender: User,
+        target: User,
+    },
+    BotAdded {
+        chat: ChatInfo,
+        operator: Option<User>,
+    },
+    BotRemoved {
+        chat: ChatInfo,
+        operator: Option<User>,
+    },
+    Other {
+        raw: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RecordedMessage {
+    id: String,
+    contents: MessageContents,
+    chat: ChatInfo,
+    sender: User,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reply_to: Option<ReplyTo>,
+}
+
+impl RecordedEvent {
+    fn from_event<C>(event: &Event<C>) -> Option<Self>
+    where
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        Some(match event.clone() {
+            Event::Message(msg) => Self::Message(RecordedMessage::from_message(msg)),
+            Event::MessageEdited(msg) => Self::MessageEdited(RecordedMessage::from_message(msg)),
+            Event::MessageRecalled {
+                chat,
+                message_id,
+                sender,
+                operator,
+            } => Self::MessageRecalled {
+                chat: chat.info,
+                message_id,
+                sender,
+                operator,
+            },
+            Event::MemberJoined {
+                chat,
+                user,
+                operator,
+            } => Self::MemberJoined {
+                chat: chat.info,
+                user,
+                operator,
+            },
+            Event::MemberLeft {
+                chat,
+                user,
+                operator,
+            } => Self::MemberLeft {
+                chat: chat.info,
+                user,
+                operator,
+            },
+            Event::FriendRequest {
+                chat,
+                user,
+                comment,
+                flag,
+            } => Self::FriendRequest {
+                chat: chat.info,
+                user,
+                comment,
+                flag,
+            },
+            Event::GroupJoinRequest {
+                chat,
+                user,
+                comment,
+                flag,
+            } => Self::GroupJoinRequest {
+                chat: chat.info,
+                user,
+                comment,
+                flag,
+            },
+            Event::Poke {
+                chat,
+                sender,
+                target,
+            } => Self::Poke {
+                chat: chat.info,
+                sender,
+                target,
+            },
+            Event::BotAdded { chat, operator } => Self::BotAdded {
+                chat: chat.info,
+                operator,
+            },
+            Event::BotRemoved { chat, operator } => Self::BotRemoved {
+                chat: chat.info,
+                operator,
+            },
+            Event::ApiStatusChanged { .. } => return None,
+            Event::Other(raw) => Self::Other { raw },
+        })
+    }
+
+    fn into_event<C>(self, api: &Arc<dyn BotAPI<C>>) -> Event<C>
+    where
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        let chat = |info| Chat::from_info(api.clone(), info);
+
+        match self {
+            Self::Message(msg) => Event::Message(msg.into_message(api)),
+            Self::MessageEdited(msg) => Event::MessageEdited(msg.into_message(api)),
+            Self::MessageRecalled {
+                chat: info,
+                message_id,
+                sender,
+                operator,
+            } => Event::MessageRecalled {
+                chat: chat(info),
+                message_id,
+                sender,
+                operator,
+            },
+            Self::MemberJoined {
+                chat: info,
+                user,
+                operator,
+            } => Event::MemberJoined {
+                chat: chat(info),
+                user,
+                operator,
+            },
+            Self::MemberLeft {
+                chat: info,
+                user,
+                operator,
+            } => Event::MemberLeft {
+                chat: chat(info),
+                user,
+                operator,
+            },
+            Self::FriendRequest {
+                chat: info,
+                user,
+                comment,
+                flag,
+            } => Event::FriendRequest {
+                chat: chat(info),
+                user,
+                comment,
+                flag,
+            },
+            Self::GroupJoinRequest {
+                chat: info,
+                user,
+                comment,
+                flag,
+            } => Event::GroupJoinRequest {
+                chat: chat(info),
+                user,
+                comment,
+                flag,
+            },
+            Self::Poke {
+                chat: info,
+                sender,
+                target,
+            } => Event::Poke {
+                chat: chat(info),
+                sender,
+                target,
+            },
+            Self::BotAdded {
+                chat: info,
+                operator,
+            } => Event::BotAdded {
+                chat: chat(info),
+                operator,
+            },
+            Self::BotRemoved {
+                chat: info,
+                operator,
+            } => Event::BotRemoved {
+                chat: chat(info),
+                operator,
+            },
+            Self::Other { raw } => Event::Other(raw),
+        }
+    }
+}
+
+impl RecordedMessage {
+    fn from_message<C>(msg: Message<C>) -> Self
+    where
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        Self {
+            id: msg.id,
+            contents: msg.contents,
+            chat: msg.chat.info,
+            sender: msg.sender,
+            reply_to: msg.reply_to.map(|reply_to| *reply_to),
+        }
+    }
+
+    fn into_message<C>(self, api: &Arc<dyn BotAPI<C>>) -> Message<C>
+    where
+        C: Clone + Debug + Send + Sync + 'static,
+    {
+        let msg = Message::new(
+            self.id,
+            self.contents,
+            Chat::from_info(api.clone(), self.chat),
+            self.sender,
+        );
+
+        match self.reply_to {
+            Some(reply_to) => msg.reply_to(reply_to),
+            None => msg,
+        }
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+pub use chrono_tz::Tz;
+use tokio::task::JoinHandle;
+use tokio_util::task::TaskTracker;
+
+use crate::api::BotAPI;
+use crate::{Chat, Runtime};
+
+#[derive(Clone, Debug)]
+pub enum Trigger {
+    Cron {
+        schedule: Box<cron::Schedule>,
+        tz: Tz,
+    },
+    Interval(Duration),
+    Once(DateTime<Utc>),
+}
+
+impl Trigger {
+    /// Accepts both 5-field (`min hour day month weekday`) and the 6 or
+    /// 7-field expressions with seconds (and years) of the `cron` crate. In 5
+    /// fields, weekdays are numbered as in standard cron, 0 to 7 with both 0
+    /// and 7 being Sunday, while the `cron` crate numbers them 1 to 7 from
+    /// Sunday.
+    ///
+    /// # Errors
+    pub fn cron(expr: &str, tz: Tz) -> Result<Self> {
+        let fields: Vec<_> = expr.split_whitespace().collect();
+        let expr = if let [minute, hour, day, month, weekday] = fields[..] {
+            let weekday = translate_weekdays(weekday)
+                .with_context(|| format!("failed to parse cron expression `{expr}`"))?;
+            format!("0 {minute} {hour} {day} {month} {weekday}")
+        } else {
+            expr.to_owned()
+        };
+
+        Ok(Self::Cron {
+            schedule: Box::new(
+                cron::Schedule::from_str(&expr)
+                    .with_context(|| format!("failed to parse cron expression `{expr}`"))?,
+            ),
+            tz,
+        })
+    }
+
+    #[must_use]
+    pub const fn every(interval: Duration) -> Self {
+        Self::Interval(interval)
+    }
+
+    #[must_use]
+    pub fn at<Z>(time: &DateTime<Z>) -> Self
+    where
+        Z: TimeZone,
+    {
+        Self::Once(time.with_timezone(&Utc))
+    }
+
+    fn next(&self, previous: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Cron { schedule, tz } => schedule
+                .after(&previous.unwrap_or_else(Utc::now).with_timezone(tz))
+                .next()
+                .map(|time| time.with_timezone(&Utc)),
+            Self::Interval(interval) => Some(
+                previous.unwrap_or_else(Utc::now) + chrono::Duration::from_std(*interval).ok()?,
+            ),
+            Self::Once(time) => previous.is_none().then_some(*time),
+        }
+    }
+}
+
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// Translates a standard weekday field, e.g. `1-5` or `0,6`, into the names
+/// of the days, which the `cron` crate takes regardless of its numbering.
+fn translate_weekdays(field: &str) -> Result<String> {
+    if field == "*" || field == "?" {
+        return Ok(field.to_owned());
+    }
+
+    let mut days = [false; 7];
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .with_context(|| format!("invalid step `{step}`"))?,
+            ),
+            None => (item, 1),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (0, 6),
+            Some((start, end)) => (parse_weekday(start)?, parse_weekday(end)?),
+            None if step > 1 => (parse_weekday(range)?, 6),
+            None => {
+                let day = parse_weekday(range)?;
+                (day, day)
+            },
+        };
+        anyhow::ensure!(start <= end, "invalid weekday range `{range}`");
+
+        for day in (start..=end).step_by(step) {
+            days[day % 7] = true;
+        }
+    }
+
+    Ok(WEEKDAYS
+        .iter()
+        .zip(days)
+        .filter_map(|(name, day)| day.then_some(*name))
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+/// Parses a weekday from 0 to 7 or its name, e.g. `Mon`.
+fn parse_weekday(s: &str) -> Result<usize> {
+    s.parse()
+        .ok()
+        .filter(|day| *day <= 7)
+        .or_else(|| {
+            WEEKDAYS
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(s))
+        })
+        .with_context(|| format!("invalid weekday `{s}`"))
+}
+
+impl Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cron { schedule, tz } => write!(f, "cron `{schedule}` ({tz})"),
+            Self::Interval(interval) => write!(f, "every {interval:?}"),
+            Self::Once(time) => write!(f, "once at {time}"),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub trait Job<C>: Send + Sync + 'static
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    async fn run(&self, ctx: JobContext<C>) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<C, F, Fut> Job<C> for F
+where
+    C: Clone + Debug + Send + Sync + 'static,
+    F: Fn(JobContext<C>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    async fn run(&self, ctx: JobContext<C>) -> Result<()> {
+        self(ctx).await
+    }
+}
+
+/// What a job runs with: the APIs to send with, and the chats to send to.
+#[derive(Clone)]
+pub struct JobContext<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    runtime: Arc<Runtime<C>>,
+}
+
+impl<C> JobContext<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    #[must_use]
+    pub fn get_apis(&self) -> &[Arc<dyn BotAPI<C>>] {
+        self.runtime.apis.as_slice()
+    }
+
+    /// Like [`Chat::from_raw`], but the chat also has its
+    /// [`storage`](Chat::storage) and [permissions](Chat::has_permission).
+    #[must_use]
+    pub fn chat(&self, api: Arc<dyn BotAPI<C>>, chat_type: i32, chat_id: String) -> Chat<C> {
+        Chat {
+            runtime: Some(self.runtime.clone()),
+            ..Chat::from_raw(api, chat_type, chat_id)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct JobId(u64);
+
+impl Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub name: String,
+    pub trigger: Trigger,
+    pub next_run: Option<DateTime<Utc>>,
+    pub running: bool,
+}
+
+/// Runs jobs on cron expressions, fixed intervals or at one-shot times.
+///
+/// Obtained from [`BotMaidBuilder::scheduler`](crate::BotMaidBuilder::scheduler)
+/// and usable both before and while the bot runs. A job is skipped if its
+/// previous run has not finished yet.
+pub struct Scheduler<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    next_id: AtomicU64,
+    entries: Mutex<HashMap<JobId, Entry<C>>>,
+
+    runtime: OnceLock<Arc<Runtime<C>>>,
+    tracker: TaskTracker,
+}
+
+struct Entry<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    name: String,
+    trigger: Trigger,
+    job: Arc<dyn Job<C>>,
+    state: Arc<State>,
+
+    task: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct State {
+    running: AtomicBool,
+    next_run: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl State {
+    /// Marks the job as running, or returns `None` if it already is.
+    fn start_run(self: &Arc<Self>) -> Option<RunGuard> {
+        (!self.running.swap(true, Ordering::AcqRel)).then(|| RunGuard(self.clone()))
+    }
+}
+
+/// Marks the job as no longer running when dropped, even if the run panics.
+struct RunGuard(Arc<State>);
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        self.0.running.store(false, Ordering::Release);
+    }
+}
+
+impl<C> Scheduler<C>
+where
+    C: Clone + Debug + Send + Sync + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            entries: Mutex::new(HashMap::new()),
+
+            runtime: OnceLock::new(),
+            tracker: TaskTracker::new(),
+        }
+    }
+
+    pub fn add_job<J>(self: &Arc<Self>, name: &str, trigger: Trigger, job: J) -> JobId
+    where
+        J: Job<C>,
+    {
+        let id = JobId(self.next_id.fetch_add(1, Ordering::Relaxed));
+
+        // Inserted before spawning, so a one-shot job finishing right away
+        // can always remove itself.
+        let mut entries = self.lock();
+        let entry = entries.entry(id).or_insert(Entry {
+            name: name.to_owned(),
+            trigger,
+            job: Arc::new(job),
+            state: Arc::new(State::default()),
+
+            task: None,
+        });
+        if let Some(runtime) = self.runtime.get() {
+            entry.task = Some(self.spawn(id, entry, runtime.clone()));
+        }
+        drop(entries);
+
+        id
+    }
+
+    /// Returns whether the job existed. A run in progress is not interrupted.
+    pub fn cancel(&self, id: JobId) -> bool {
+        let Some(entry) = self.lock().remove(&id) else {
+            return false;
+        };
+
+        if let Some(task) = entry.task {
+            task.abort();
+        }
+
+        true
+    }
+
+    #[must_use]
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<_> = self
+            .lock()
+            .iter()
+            .map(|(id, entry)| JobInfo {
+                id: *id,
+                name: entry.name.clone(),
+                trigger: entry.trigger.clone(),
+                next_run: *lock(&entry.state.next_run),
+                running: entry.state.running.load(Ordering::Acquire),
+            })
+            .collect();
+        jobs.sort_by_key(|job| job.id.0);
+
+        jobs
+    }
+
+    pub(crate) fn start(self: &Arc<Self>, runtime: &Arc<Runtime<C>>) {
+        if self.runtime.set(runtime.clone()).is_err() {
+            tracing::error!("scheduler is already started");
+            return;
+        }
+
+        let mut entries = self.lock();
+        for (id, entry) in entries.iter_mut() {
+            entry.task = Some(self.spawn(*id, entry, runtime.clone()));
+        }
+    }
+
+    /// Stops triggering jobs and waits for the runs in progress.
+    pub(crate) async fn stop(&self) {
+        for entry in self.lock().values_mut() {
+            if let Some(task) = entry.task.take() {
+                task.abort();
+            }
+        }
+
+        self.tracker.close();
+        self.tracker.wait().await;
+    }
+
+    fn spawn(
+        self: &Arc<Self>,
+        id: JobId,
+        entry: &Entry<C>,
+        runtime: Arc<Runtime<C>>,
+    ) -> JoinHandle<()> {
+        let self_clone = self.clone();
+        let name = entry.name.clone();
+        let trigger = entry.trigger.clone();
+        let job = entry.job.clone();
+        let state = entry.state.clone();
+
+        tokio::spawn(async move {
+            let mut previous = None;
+            let mut last_run = None;
+            while let Some(next) = trigger.next(previous) {
+                *lock(&state.next_run) = Some(next);
+                tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+                previous = Some(next);
+
+                let Some(guard) = state.start_run() else {
+                    tracing::warn!("job `{name}` is still running, skipping this run");
+                    continue;
+                };
+
+                let name = name.clone();
+                let job = job.clone();
+                let ctx = JobContext {
+                    runtime: runtime.clone(),
+                };
+                last_run = Some(self_clone.tracker.spawn(async move {
+                    tracing::info!("running job `{name}`");
+                    if let Err(err) = job.run(ctx).await {
+                        tracing::error!("job `{name}` failed: {err:?}");
+                    }
+                    drop(guard);
+                }));
+            }
+
+            // Listed until the last run finishes
+            *lock(&state.next_run) = None;
+            if let Some(run) = last_run {
+                let _ = run.await;
+            }
+            self_clone.lock().remove(&id);
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<JobId, Entry<C>>> {
+        lock(&self.entries)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate_weekdays_from_sunday_as_0() {
+        for (field, days) in [
+            ("*", "*"),
+            ("?", "?"),
+            ("0", "SUN"),
+            ("7", "SUN"),
+            ("1-5", "MON,TUE,WED,THU,FRI"),
+            ("0,6", "SUN,SAT"),
+            ("5-7", "SUN,FRI,SAT"),
+            ("*/2", "SUN,TUE,THU,SAT"),
+            ("1/3", "MON,THU"),
+            ("mon-Wed,sat", "MON,TUE,WED,SAT"),
+        ] {
+            assert_eq!(translate_weekdays(field).unwrap(), days, "{field}");
+        }
+
+        for field in ["8", "5-1", "1/0", "x", ""] {
+            assert!(translate_weekdays(field).is_err(), "{field}");
+        }
+    }
+
+    #[test]
+    fn cron_with_5_fields_runs_on_the_weekday() {
+        let trigger = Trigger::cron("30 9 * * 1", Tz::UTC).unwrap();
+        let monday = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            trigger.next(Some(monday)),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 9, 30, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn skip_runs_while_running() {
+        let state = Arc::new(State::default());
+
+        let guard = state.start_run().unwrap();
+        assert!(state.start_run().is_none());
+        drop(guard);
+        assert!(state.start_run().is_some());
+    }
+
+    #[tokio::test]
+    async fn panicked_run_is_no_longer_running() {
+        let state = Arc::new(State::default());
+
+        let guard = state.start_run().unwrap();
+        let run = tokio::spawn(async move {
+            let _guard = guard;
+            panic!("job panicked");
+        });
+        assert!(run.await.is_err());
+
+        assert!(!state.running.load(Ordering::Acquire));
+        assert!(state.start_run().is_some());
+    }
+}
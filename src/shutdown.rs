@@ -0,0 +1,44 @@
+use anyhow::{Context, Result};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio_util::sync::CancellationToken;
+
+/// Stops a running [`BotMaid`](crate::BotMaid) when triggered.
+///
+/// Obtained from [`BotMaidBuilder::shutdown_handle`](crate::BotMaidBuilder::shutdown_handle).
+/// Long-running jobs can also await [`ShutdownHandle::cancelled`] to finish
+/// early.
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownHandle {
+    token: CancellationToken,
+}
+
+impl ShutdownHandle {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn shutdown(&self) {
+        self.token.cancel();
+    }
+
+    #[must_use]
+    pub fn is_shutdown(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await;
+    }
+}
+
+pub(crate) async fn wait_for_signal() -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate()).context("failed to listen for SIGTERM")?;
+
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res.context("failed to listen for SIGINT")?,
+        _ = terminate.recv() => {},
+    }
+
+    Ok(())
+}
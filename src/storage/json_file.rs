@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use super::Storage;
+
+type Scopes = HashMap<String, HashMap<String, Value>>;
+
+/// Keeps everything in memory and rewrites a single JSON file on every
+/// change. Fits bots with a modest amount of data.
+pub struct JsonFile {
+    path: PathBuf,
+    scopes: Mutex<Scopes>,
+}
+
+impl JsonFile {
+    /// Loads `path`, or starts empty if it does not exist yet.
+    ///
+    /// # Errors
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_owned();
+
+        let scopes = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .with_context(|| format!("failed to parse {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Scopes::new(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()));
+            },
+        };
+
+        Ok(Self {
+            path,
+            scopes: Mutex::new(scopes),
+        })
+    }
+
+    async fn save(&self, scopes: &Scopes) -> Result<()> {
+        let data = serde_json::to_vec(scopes).context("failed to serialize storage")?;
+
+        // Written aside and renamed over so a crash never leaves a torn file.
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, data)
+            .await
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .with_context(|| format!("failed to replace {}", self.path.display()))
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for JsonFile {
+    async fn get(&self, scope: &str, key: &str) -> Result<Option<Value>> {
+        Ok(self
+            .scopes
+            .lock()
+            .await
+            .get(scope)
+            .and_then(|values| values.get(key))
+            .cloned())
+    }
+
+    async fn set(&self, scope: &str, key: &str, value: Value) -> Result<()> {
+        let mut scopes = self.scopes.lock().await;
+
+        scopes
+            .entry(scope.to_owned())
+            .or_default()
+            .insert(key.to_owned(), value);
+
+        // Kept locked while saving so that writes land in order.
+        let res = self.save(&scopes).await;
+        drop(scopes);
+        res
+    }
+
+    async fn remove(&self, scope: &str, key: &str) -> Result<()> {
+        let mut scopes = self.scopes.lock().await;
+
+        let Some(values) = scopes.get_mut(scope) else {
+            return Ok(());
+        };
+        if values.remove(key).is_none() {
+            return Ok(());
+        }
+        if values.is_empty() {
+            scopes.remove(scope);
+        }
+
+        let res = self.save(&scopes).await;
+        drop(scopes);
+        res
+    }
+}
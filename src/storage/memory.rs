@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use super::Storage;
+
+/// Keeps everything in memory, so nothing survives a restart.
+#[derive(Default)]
+pub struct Memory {
+    scopes: Mutex<HashMap<String, HashMap<String, Value>>>,
+}
+
+impl Memory {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, HashMap<String, Value>>> {
+        self.scopes
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for Memory {
+    async fn get(&self, scope: &str, key: &str) -> Result<Option<Value>> {
+        Ok(self
+            .lock()
+            .get(scope)
+            .and_then(|values| values.get(key))
+            .cloned())
+    }
+
+    async fn set(&self, scope: &str, key: &str, value: Value) -> Result<()> {
+        self.lock()
+            .entry(scope.to_owned())
+            .or_default()
+            .insert(key.to_owned(), value);
+
+        Ok(())
+    }
+
+    async fn remove(&self, scope: &str, key: &str) -> Result<()> {
+        if let Entry::Occupied(mut values) = self.lock().entry(scope.to_owned()) {
+            values.get_mut().remove(key);
+            if values.get().is_empty() {
+                values.remove();
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub mod json_file;
+pub mod memory;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// A key-value store of JSON values, grouped into scopes.
+///
+/// Usually accessed through [`Chat::storage`](crate::Chat::storage) and
+/// [`Chat::user_storage`](crate::Chat::user_storage) rather than directly.
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync + 'static {
+    /// # Errors
+    async fn get(&self, scope: &str, key: &str) -> Result<Option<Value>>;
+
+    /// # Errors
+    async fn set(&self, scope: &str, key: &str, value: Value) -> Result<()>;
+
+    /// # Errors
+    async fn remove(&self, scope: &str, key: &str) -> Result<()>;
+}
+
+/// Typed access to one scope of a [`Storage`].
+#[derive(Clone)]
+pub struct Scope {
+    storage: Arc<dyn Storage>,
+    name: String,
+}
+
+impl Scope {
+    #[must_use]
+    pub fn new(storage: Arc<dyn Storage>, name: String) -> Self {
+        Self { storage, name }
+    }
+
+    /// Narrows this scope, e.g. to keep the data of different plugins apart.
+    #[must_use]
+    pub fn scope(&self, name: &str) -> Self {
+        Self::new(self.storage.clone(), format!("{}/{name}", self.name))
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// # Errors
+    pub async fn get<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.storage
+            .get(&self.name, key)
+            .await?
+            .map(|value| {
+                serde_json::from_value(value)
+                    .with_context(|| format!("failed to deserialize `{key}` in `{}`", self.name))
+            })
+            .transpose()
+    }
+
+    /// # Errors
+    pub async fn set<T>(&self, key: &str, value: &T) -> Result<()>
+    where
+        T: Serialize + Sync + ?Sized,
+    {
+        let value = serde_json::to_value(value)
+            .with_context(|| format!("failed to serialize `{key}` in `{}`", self.name))?;
+
+        self.storage.set(&self.name, key, value).await
+    }
+
+    /// # Errors
+    pub async fn remove(&self, key: &str) -> Result<()> {
+        self.storage.remove(&self.name, key).await
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::Value;
+
+use super::Storage;
+
+/// Stores values in an `SQLite` database, one row per key.
+pub struct Sqlite {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Sqlite {
+    /// Opens or creates the database at `path`.
+    ///
+    /// # Errors
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        let conn =
+            Connection::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS storage (
+                scope TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (scope, key)
+            )",
+            (),
+        )
+        .context("failed to create the storage table")?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    async fn with_conn<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Connection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+
+        tokio::task::spawn_blocking(move || {
+            f(&conn
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner))
+        })
+        .await?
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for Sqlite {
+    async fn get(&self, scope: &str, key: &str) -> Result<Option<Value>> {
+        let (scope, key) = (scope.to_owned(), key.to_owned());
+
+        let value: Option<String> = self
+            .with_conn(move |conn| {
+                conn.query_row(
+                    "SELECT value FROM storage WHERE scope = ?1 AND key = ?2",
+                    (&scope, &key),
+                    |row| row.get(0),
+                )
+                .optional()
+                .context("failed to query storage")
+            })
+            .await?;
+
+        value
+            .map(|value| serde_json::from_str(&value).context("failed to parse stored value"))
+            .transpose()
+    }
+
+    async fn set(&self, scope: &str, key: &str, value: Value) -> Result<()> {
+        let (scope, key, value) = (scope.to_owned(), key.to_owned(), value.to_string());
+
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO storage (scope, key, value) VALUES (?1, ?2, ?3)
+                ON CONFLICT (scope, key) DO UPDATE SET value = excluded.value",
+                (&scope, &key, &value),
+            )
+            .context("failed to write storage")?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove(&self, scope: &str, key: &str) -> Result<()> {
+        let (scope, key) = (scope.to_owned(), key.to_owned());
+
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM storage WHERE scope = ?1 AND key = ?2",
+                (&scope, &key),
+            )
+            .context("failed to write storage")?;
+
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::VecDeque;
+
+use crate::{MessageContent, MessageContents, User};
+
+/// How [`MessageContents::plain_text`](crate::MessageContents::plain_text)
+/// renders mentions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mention {
+    /// The id of the user, e.g. `12345`.
+    Id,
+    /// `@` and the nickname of the user, or their id if they have none.
+    Nickname,
+    /// Nothing at all.
+    Omit,
+}
+
+impl Mention {
+    pub(crate) fn render(self, user: &User) -> Option<String> {
+        match self {
+            Self::Id => Some(user.get_id().clone()),
+            Self::Nickname => Some(match user.get_nickname() {
+                "" => format!("@{}", user.get_id()),
+                nickname => format!("@{nickname}"),
+            }),
+            Self::Omit => None,
+        }
+    }
+}
+
+/// An argument split by [`MessageContents::args`](crate::MessageContents::args).
+#[derive(Clone, Debug)]
+pub enum Arg {
+    Text(String),
+    /// A content other than text, such as a mention or an image, which is an
+    /// argument by itself.
+    Content(MessageContent),
+}
+
+impl Arg {
+    #[must_use]
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            Self::Content(_) => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn as_user(&self) -> Option<&User> {
+        match self {
+            Self::Content(MessageContent::At(user)) => Some(user),
+            _ => None,
+        }
+    }
+}
+
+/// Splits text into arguments like a shell, with single and double quotes and
+/// backslash escapes. Quotes only open at the start of an argument, so that
+/// apostrophes in words such as `don't` are kept. The text can be fed a piece
+/// at a time.
+#[derive(Default)]
+pub(crate) struct Tokenizer {
+    tokens: Vec<String>,
+    token: String,
+    in_token: bool,
+    /// The open quote and the raw text after it, kept to be fed again if the
+    /// quote is never closed.
+    quote: Option<(char, String)>,
+    escaped: bool,
+}
+
+impl Tokenizer {
+    pub(crate) fn feed(&mut self, s: &str) {
+        for c in s.chars() {
+            if let Some((_, raw)) = &mut self.quote {
+                raw.push(c);
+            }
+
+            if self.escaped {
+                self.token.push(c);
+                self.escaped = false;
+                continue;
+            }
+
+            match (self.quote.as_ref().map(|(q, _)| *q), c) {
+                (Some(q), c) if c == q => self.quote = None,
+                (Some('"') | None, '\\') => {
+                    self.escaped = true;
+                    self.in_token = true;
+                },
+                (Some(_), c) => self.token.push(c),
+                (None, '"' | '\'') if !self.in_token => {
+                    self.quote = Some((c, String::new()));
+                    self.in_token = true;
+                },
+                (None, c) if c.is_whitespace() => self.end_token(),
+                (None, c) => {
+                    self.token.push(c);
+                    self.in_token = true;
+                },
+            }
+        }
+    }
+
+    /// Ends the current argument and takes all the arguments so far. A quote
+    /// left open is taken literally.
+    pub(crate) fn take(&mut self) -> Vec<String> {
+        while let Some((q, raw)) = self.quote.take() {
+            // Opened at the start of the argument, so the argument is all quoted
+            self.token.clear();
+            self.token.push(q);
+            self.escaped = false;
+            self.feed(&raw);
+        }
+
+        self.escaped = false;
+        self.end_token();
+
+        std::mem::take(&mut self.tokens)
+    }
+
+    fn end_token(&mut self) {
+        if self.in_token {
+            self.tokens.push(std::mem::take(&mut self.token));
+            self.in_token = false;
+        }
+    }
+}
+
+/// Splits `s` into arguments, see [`Tokenizer`]. A quote left open is taken
+/// literally.
+pub(crate) fn tokenize(s: &str) -> Vec<String> {
+    let mut tokenizer = Tokenizer::default();
+    tokenizer.feed(s);
+    tokenizer.take()
+}
+
+/// See [`MessageContents::split`](crate::MessageContents::split).
+pub(crate) fn split(contents: Vec<MessageContent>, max_length: usize) -> Vec<MessageContents> {
+    let mut parts = Vec::new();
+    let mut part = Vec::new();
+    let mut length = 0;
+
+    let mut contents = VecDeque::from(contents);
+    while let Some(content) = contents.pop_front() {
+        let content_length = get_length(&content);
+        if length + content_length <= max_length {
+            part.push(content);
+            length += content_length;
+            continue;
+        }
+
+        match cut(&content, max_length - length, part.is_empty()) {
+            Some((head, tail)) => {
+                if !is_empty_text(&head) {
+                    part.push(head);
+                }
+                contents.push_front(tail);
+            },
+            // Too long by itself, sent alone anyway
+            None if part.is_empty() => part.push(content),
+            None => contents.push_front(content),
+        }
+
+        if !part.is_empty() {
+            parts.push(MessageContents(std::mem::take(&mut part)));
+            length = 0;
+        }
+    }
+
+    if !part.is_empty() || parts.is_empty() {
+        parts.push(MessageContents(part));
+    }
+
+    parts
+}
+
+/// The length in UTF-16 code units as Telegram counts, mentions as
+/// `@nickname` and media as nothing.
+fn get_length(content: &MessageContent) -> usize {
+    match content {
+        MessageContent::Text(text) | MessageContent::Styled { text, .. } => {
+            text.encode_utf16().count()
+        },
+        MessageContent::At(user) => Mention::Nickname
+            .render(user)
+            .map_or(0, |mention| mention.encode_utf16().count()),
+        MessageContent::Forward(_) => content.to_string().encode_utf16().count(),
+        MessageContent::Image(_) |
+        MessageContent::Voice(_) |
+        MessageContent::Video(_) |
+        MessageContent::File(_) => 0,
+    }
+}
+
+/// Cuts a text so that its head is at most `max_length`. Styled text is only
+/// cut if `alone` in its part, i.e. too long by itself, while other contents
+/// are never cut.
+fn cut(
+    content: &MessageContent,
+    max_length: usize,
+    alone: bool,
+) -> Option<(MessageContent, MessageContent)> {
+    match content {
+        MessageContent::Text(text) => {
+            let (end, start) = cut_text(text, max_length, alone)?;
+            Some((
+                MessageContent::Text(text[..end].to_owned()),
+                MessageContent::Text(text[start..].to_owned()),
+            ))
+        },
+        MessageContent::Styled { text, style } if alone => {
+            let (end, start) = cut_text(text, max_length, true)?;
+            Some((
+                MessageContent::Styled {
+                    text: text[..end].to_owned(),
+                    style: style.clone(),
+                },
+                MessageContent::Styled {
+                    text: text[start..].to_owned(),
+                    style: style.clone(),
+                },
+            ))
+        },
+        _ => None,
+    }
+}
+
+/// Finds the end of the head and the start of the tail, cutting at the last
+/// line break that fits, or else the last whitespace, which is dropped. Only
+/// cuts amid a word if `force`.
+fn cut_text(text: &str, max_length: usize, force: bool) -> Option<(usize, usize)> {
+    let mut line = None;
+    let mut word = None;
+    let mut end = 0;
+    let mut length = 0;
+
+    for (i, c) in text.char_indices() {
+        if c == '\n' {
+            line = Some((i, i + 1));
+        } else if c.is_whitespace() {
+            word = Some((i, i + c.len_utf8()));
+        }
+
+        length += c.len_utf16();
+        if length > max_length {
+            break;
+        }
+        end = i + c.len_utf8();
+    }
+
+    line.or(word).or_else(|| {
+        // At least a char to make progress
+        let end = text.chars().next().map_or(end, |c| end.max(c.len_utf8()));
+        force.then_some((end, end))
+    })
+}
+
+const fn is_empty_text(content: &MessageContent) -> bool {
+    matches!(content, MessageContent::Text(text) | MessageContent::Styled { text, .. } if text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_quotes_and_escapes() {
+        assert_eq!(
+            tokenize(r#"a  'b c' "d \"e\"" f\ g 'h\i'"#),
+            ["a", "b c", r#"d "e""#, "f g", r"h\i"]
+        );
+        assert_eq!(tokenize(r#"x"y z"#), [r#"x"y"#, "z"]);
+        assert_eq!(tokenize("\"\" ''"), ["", ""]);
+    }
+
+    #[test]
+    fn tokenize_apostrophes_in_words() {
+        assert_eq!(tokenize("say don't"), ["say", "don't"]);
+        assert_eq!(tokenize("what's up"), ["what's", "up"]);
+    }
+
+    #[test]
+    fn tokenize_unterminated_quotes() {
+        assert_eq!(tokenize("say 'hi there"), ["say", "'hi", "there"]);
+        assert_eq!(tokenize(r#"a "b 'c d"#), ["a", r#""b"#, "'c", "d"]);
+        assert_eq!(tokenize(r#""a \" b"#), [r#""a"#, r#"""#, "b"]);
+        assert_eq!(tokenize("'a' 'b"), ["a", "'b"]);
+    }
+
+    fn split_text(contents: Vec<MessageContent>, max_length: usize) -> Vec<String> {
+        split(contents, max_length)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    fn text(text: &str) -> MessageContent {
+        MessageContent::Text(text.to_owned())
+    }
+
+    #[test]
+    fn split_at_exact_limits() {
+        assert_eq!(split_text(vec![text("abcde")], 5), ["abcde"]);
+        assert_eq!(split_text(vec![text("abc"), text("de")], 5), ["abcde"]);
+        assert_eq!(split_text(vec![text("abcdef")], 5), ["abcde", "f"]);
+        assert_eq!(split_text(vec![text("hello world")], 5), ["hello", "world"]);
+        assert_eq!(split_text(vec![], 5), [""]);
+    }
+
+    #[test]
+    fn split_at_lines_then_words() {
+        assert_eq!(
+            split_text(vec![text("ab cd\nef gh")], 8),
+            ["ab cd", "ef gh"]
+        );
+        assert_eq!(
+            split_text(vec![text("one two three")], 9),
+            ["one two", "three"]
+        );
+    }
+
+    #[test]
+    fn split_surrogate_pairs() {
+        // Each is 2 UTF-16 code units
+        assert_eq!(split_text(vec![text("😀😀😀")], 5), ["😀😀", "😀"]);
+        assert_eq!(split_text(vec![text("😀😀")], 4), ["😀😀"]);
+        // Too long by itself, so cut after it to make progress
+        assert_eq!(split_text(vec![text("😀x")], 1), ["😀", "x"]);
+    }
+
+    #[test]
+    fn split_word_longer_than_limit() {
+        assert_eq!(
+            split_text(vec![text("a verylongword")], 5),
+            ["a", "veryl", "ongwo", "rd"]
+        );
+    }
+
+    #[test]
+    fn split_keeps_other_contents_whole() {
+        let bold = |text: &str| MessageContent::Styled {
+            text: text.to_owned(),
+            style: crate::Style::Bold,
+        };
+        let image = MessageContent::Image(crate::MediaSource::Url("u".to_owned()));
+
+        assert_eq!(
+            split_text(vec![text("abc "), bold("defgh")], 6),
+            ["abc ", "defgh"]
+        );
+        assert_eq!(split_text(vec![bold("abcdefgh")], 6), ["abcdef", "gh"]);
+        assert_eq!(
+            split_text(vec![text("abcdef"), image], 6),
+            ["abcdef[image: u]"]
+        );
+        assert_eq!(
+            split_text(
+                vec![
+                    text("hi "),
+                    MessageContent::At(User::new("1".to_owned()).nickname("bob".to_owned())),
+                ],
+                6
+            ),
+            ["hi ", "@bob(1) "]
+        );
+    }
+
+    #[test]
+    fn cut_text_boundaries() {
+        assert_eq!(cut_text("ab cd", 3, false), Some((2, 3)));
+        assert_eq!(cut_text("ab\ncd ef", 7, false), Some((2, 3)));
+        assert_eq!(cut_text("abcdef", 3, false), None);
+        assert_eq!(cut_text("abcdef", 3, true), Some((3, 3)));
+        assert_eq!(cut_text("😀😀", 3, true), Some((4, 4)));
+    }
+
+    #[test]
+    fn take_keeps_unterminated_quotes() {
+        let mut tokenizer = Tokenizer::default();
+        tokenizer.feed("a 'b");
+        assert_eq!(tokenizer.take(), ["a", "'b"]);
+
+        tokenizer.feed("don't 'b");
+        tokenizer.feed(" c'");
+        assert_eq!(tokenizer.take(), ["don't", "b c"]);
+    }
+
+    #[test]
+    fn args_split_as_tokenize() {
+        for s in [r#"a 'b c' "d \"e\"""#, "don't 'b", r#"a "b c"#, "a\\"] {
+            let args: Vec<_> = MessageContents::new()
+                .text(s)
+                .args()
+                .iter()
+                .filter_map(|arg| arg.as_text().map(str::to_owned))
+                .collect();
+            assert_eq!(args, tokenize(s), "{s}");
+        }
+
+        let user = User::new("1".to_owned());
+        let args = MessageContents::new()
+            .text("a \"b")
+            .at(user)
+            .text("c\"")
+            .args();
+        assert_eq!(
+            args[..2]
+                .iter()
+                .map(|arg| arg.as_text())
+                .collect::<Vec<_>>(),
+            [Some("a"), Some("\"b")]
+        );
+        assert_eq!(args[3].as_text(), Some("c\""));
+    }
+}